@@ -25,6 +25,21 @@ pub enum Error {
 
     /// epoll already added
     EpollAlreadyAdded,
+
+    /// error in a keymap, keymap is the index into keymaps, line and column are where token starts inside it
+    Keymap {
+        keymap: usize,
+        line: usize,
+        column: usize,
+        token: String,
+        reason: String,
+    },
+
+    /// invalid config outside of the keymaps
+    Config(String),
+
+    /// more than one error, like every problem found in a config
+    Errors(Vec<Error>),
 }
 
 impl From<ffi::NulError> for Error {
@@ -50,20 +65,44 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         match self {
             #[cfg(target_os = "linux")]
-            &Error::Nix(ref err) => err.fmt(f),
+            Error::Nix(err) => err.fmt(f),
 
-            &Error::Nul(ref err) => err.fmt(f),
+            Error::Nul(err) => err.fmt(f),
 
-            &Error::Io(ref err) => err.fmt(f),
+            Error::Io(err) => err.fmt(f),
 
             #[cfg(feature = "toml_serde")]
-            &Error::Toml(ref err) => err.fmt(f),
-
-            &Error::NotAKeyboard => f.write_str("This device file is not a keyboard"),
-
-            &Error::ShortRead => f.write_str("Error while reading from device file."),
-
-            &Error::EpollAlreadyAdded => f.write_str("epoll already added, delete first"),
+            Error::Toml(err) => err.fmt(f),
+
+            Error::NotAKeyboard => f.write_str("This device file is not a keyboard"),
+
+            Error::ShortRead => f.write_str("Error while reading from device file."),
+
+            Error::EpollAlreadyAdded => f.write_str("epoll already added, delete first"),
+
+            Error::Keymap {
+                keymap,
+                line,
+                column,
+                token,
+                reason,
+            } => write!(
+                f,
+                "keymap {} line {} column {}: {}: {}",
+                keymap, line, column, reason, token
+            ),
+
+            Error::Config(reason) => write!(f, "invalid config: {}", reason),
+
+            Error::Errors(errors) => {
+                for (i, err) in errors.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str("\n")?;
+                    }
+                    err.fmt(f)?;
+                }
+                Ok(())
+            }
         }
     }
 }
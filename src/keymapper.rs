@@ -1,18 +1,48 @@
-use std::{collections::HashMap, convert::TryFrom, hash::Hash};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    convert::TryFrom,
+    hash::Hash,
+    time::{Duration, Instant},
+};
 
 #[cfg(feature = "toml_serde")]
 use std::path::Path;
 
-use crate::Result;
+use crate::{Error, Result};
 
 const INVERT_KEY_FLAG: char = '^';
 const CAPS_MODIFY_KEY_FLAG: char = '*';
 const HALF_KEY_SEPARATOR: char = ':';
+const KEY_SEPARATOR: char = ',';
+const KEY_CODE_PREFIX: char = '#';
+const FUNCTION_OPEN: char = '(';
+const FUNCTION_CLOSE: char = ')';
+
+const TAP_HOLD_FUNCTION: &str = "TH";
+const MOMENTARY_LAYER_FUNCTION: &str = "MO";
+const TOGGLE_LAYER_FUNCTION: &str = "TG";
+const ONE_SHOT_LAYER_FUNCTION: &str = "OSL";
+const TO_LAYER_FUNCTION: &str = "TO";
+const TRANSPARENT_KEY: &str = "TRNS";
+const MACRO_FUNCTION: &str = "M";
+const MACRO_CHORD_SEPARATOR: char = '+';
+const MACRO_DOWN_FUNCTION: &str = "DOWN";
+const MACRO_UP_FUNCTION: &str = "UP";
+const MACRO_DELAY_FUNCTION: &str = "DELAY";
+const UNICODE_PREFIX: &str = "U+";
+const DEAD_KEY_PREFIX: &str = "DEAD_";
+// names in mod_rules mods for either side's modifier
+const MODIFIER_GROUPS: [(&str, [&str; 2]); 4] = [
+    ("CTRL", ["LCTL", "RCTL"]),
+    ("SHIFT", ["LSFT", "RSFT"]),
+    ("ALT", ["LALT", "RALT"]),
+    ("GUI", ["LGUI", "RGUI"]),
+];
 
 // nightly only...
 //pub trait KeyCode = Into<usize> + TryFrom<usize> + Copy + Clone + Eq + Hash + Default + 'static;
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum KeyState {
     DOWN,
     UP,
@@ -25,6 +55,9 @@ where
 {
     fn code(&self) -> T;
     fn value(&self) -> KeyState;
+    // these are used to replay events we had to hold back, like while deciding if a tap-hold key is tapped or held
+    fn set_code(&mut self, code: T);
+    fn set_value(&mut self, value: KeyState);
 }
 
 pub trait Keyboard<T, E, R = ()>
@@ -41,6 +74,16 @@ where
     fn caps_lock_code(&self) -> T;
     fn block_key(&self) -> Result<R>;
 
+    /// Whether tick() is called once timeout() passes, platforms that don't keep the keyboard's own auto-repeat
+    fn drives_tick(&self) -> bool {
+        false
+    }
+
+    /// Moves the pointer or scrolls, platforms that can't just block the key
+    fn send_mouse_move(&self, _mouse_move: MouseMove, _amount: i32, _event: &mut E) -> Result<R> {
+        self.block_key()
+    }
+
     fn send_half_inverted_key(
         &self,
         half_inverted_key: &HalfInvertedKey<T>,
@@ -51,40 +94,33 @@ where
     ) -> Result<R> {
         let value = event.value();
         let mut invert_shift = half_inverted_key.invert_shift;
-        if value == KeyState::DOWN {
-            if caps_lock && half_inverted_key.capslock_nomodify {
-                invert_shift = !invert_shift;
-            }
-            if invert_shift {
-                let (shift_code, up_not_down) = if left_shift {
-                    (self.left_shift_code(), true)
-                } else if right_shift {
-                    (self.right_shift_code(), true)
-                } else {
-                    (self.left_shift_code(), false)
-                };
-                self.send_mod_code_value(shift_code, up_not_down, event)?;
-                // SYN_REPORT after, then key, then key's SYN_REPORT
-                self.synchronize()?;
-            }
+        if caps_lock && half_inverted_key.capslock_nomodify {
+            invert_shift = !invert_shift;
+        }
+        if value == KeyState::DOWN && invert_shift {
+            let (shift_code, up_not_down) = if left_shift {
+                (self.left_shift_code(), true)
+            } else if right_shift {
+                (self.right_shift_code(), true)
+            } else {
+                (self.left_shift_code(), false)
+            };
+            self.send_mod_code_value(shift_code, up_not_down, event)?;
+            // SYN_REPORT after, then key, then key's SYN_REPORT
+            self.synchronize()?;
         }
         let ret = self.send_mod_code(half_inverted_key.code, event)?;
-        if value == KeyState::UP {
-            if caps_lock && half_inverted_key.capslock_nomodify {
-                invert_shift = !invert_shift;
-            }
-            if invert_shift {
-                let (shift_code, up_not_down) = if left_shift {
-                    (self.left_shift_code(), false)
-                } else if right_shift {
-                    (self.right_shift_code(), false)
-                } else {
-                    (self.left_shift_code(), true)
-                };
-                // SYN_REPORT first after key, then shift, then key's SYN_REPORT which will be used for shift's
-                self.synchronize()?;
-                self.send_mod_code_value(shift_code, up_not_down, event)?;
-            }
+        if value == KeyState::UP && invert_shift {
+            let (shift_code, up_not_down) = if left_shift {
+                (self.left_shift_code(), false)
+            } else if right_shift {
+                (self.right_shift_code(), false)
+            } else {
+                (self.left_shift_code(), true)
+            };
+            // SYN_REPORT first after key, then shift, then key's SYN_REPORT which will be used for shift's
+            self.synchronize()?;
+            self.send_mod_code_value(shift_code, up_not_down, event)?;
         }
         Ok(ret)
     }
@@ -96,7 +132,12 @@ where
     E: KeyEvent<T>,
     K: Keyboard<T, E, R>,
 {
-    fn send_event(&self, key_state: &[bool], event: &mut E, device: &K) -> Result<R>;
+    fn send_event(&self, key_state: &HashSet<usize>, event: &mut E, device: &K) -> Result<R>;
+
+    // the Key mapped for code, used by KeyMaps for keys it must keep state for, like TapHold, and to describe keymaps
+    fn key(&self, _code: T) -> Option<Key<T>> {
+        None
+    }
 }
 
 pub struct KeyMaps<K, T, E, R = ()>
@@ -106,73 +147,520 @@ where
     K: Keyboard<T, E, R>,
 {
     keymaps: Vec<Box<dyn KeyMapper<K, T, E, R>>>,
+    // keymap index 0, every keymap maps these codes
+    base_keymap: Vec<T>,
+    macro_names: Vec<String>,
     keymap_index_keys: HashMap<T, usize>,
     switch_layout_keys: Vec<usize>,
-    key_state: [bool; KEY_MAX],
+    // codes currently held down, plus caps lock while it is on
+    key_state: HashSet<usize>,
     revert_default_keys: Vec<T>,
     revert_keymap_index: usize,
+    tapping_term: Duration,
+    permissive_hold: bool,
+    hold_on_other_key_press: bool,
+    macros: Vec<Vec<MacroStep<T>>>,
+    // keys typed for each unicode character used in a keymap or compose result
+    unicode_steps: HashMap<char, Vec<MacroStep<T>>>,
+    // for each dead key, the keys typed after it -> what they compose
+    dead_keys: Vec<Vec<(Vec<T>, ComposeResult<T>)>>,
+    dead_key_names: Vec<String>,
+    combos: Vec<Combo<T>>,
+    scroll_button: Option<T>,
+    // None passes on the keyboard's own auto-repeat
+    repeat: Option<Repeat<T>>,
+    // one-shot modifier -> how long it waits for the next key after being tapped, None waits forever
+    one_shot_mods: HashMap<T, Option<Duration>>,
+    mod_rules: Vec<ModRule<T>>,
+    // every modifier the key_map has, for mod_rules with only set
+    modifiers: Vec<T>,
     pub devices: DeviceMatchers,
     // above do not change, below does
     chosen_keymap_index: usize,
-    current_keymap_index: usize,
+    // true while a revert_default_keys key is held, then only revert_keymap_index is used
+    reverting: bool,
+    // layers activated by MO and TG on top of chosen_keymap_index, last is on top
+    layers: Vec<usize>,
+    // layer activated by OSL, on top of everything for the next key pressed only
+    one_shot_layer: Option<usize>,
+    // keymap index each held key was pressed on, so it is released from the same one
+    pressed_on: HashMap<T, usize>,
+    // held keys mapped to a HalfKey or FullKey -> the code the press sent, auto-repeats send just that
+    half_key_codes: HashMap<T, T>,
+    combo_pending: Option<PendingCombo<T>>,
+    // combos we sent a press for, until all their keys are released
+    active_combos: Vec<ActiveCombo<T>>,
+    tap_hold_pending: Option<PendingTapHold<T>>,
+    // physical tap-hold key code -> code we decided to send for it, until it is released
+    tap_hold_down: HashMap<T, T>,
+    // key we are auto-repeating and when it next repeats
+    repeating: Option<(T, Instant)>,
+    // one-shot modifiers that were tapped, still held down for the next key
+    one_shot_pending: Vec<OneShotMod<T>>,
+    // one-shot modifiers physically held -> whether another key was pressed while they were
+    one_shot_down: HashMap<T, bool>,
+    // the key pressed after a one-shot modifier is held back by a combo or tap-hold key, release it once that is sent
+    one_shot_deferred: bool,
+    // dead key pressed and the keys typed after it so far
+    compose_pending: Option<(usize, Vec<T>)>,
+    // keys swallowed by a compose sequence, so their release is too
+    compose_swallowed: Vec<T>,
+    // keys a mod_rules rule was pressed for -> that rule and the modifiers it let go, until they are released
+    mod_rules_down: HashMap<T, (usize, Vec<T>)>,
+    macro_pending: Option<PendingMacro<T>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MacroStep<T> {
+    Down(T),
+    Up(T),
+    Delay(Duration),
+}
+
+struct Combo<T> {
+    keys: Vec<T>,
+    code: T,
+    term: Duration,
+}
+
+struct Repeat<T> {
+    delay: Duration,
+    period: Duration,
+    // keys that never repeat, and don't stop the key that is repeating either
+    no_repeat: Vec<T>,
+}
+
+struct ModRule<T: Copy> {
+    key: T,
+    // each must be held, any one of its codes will do
+    mods: Vec<Vec<T>>,
+    // no modifiers other than mods may be held
+    only: bool,
+    // mods are let go while send is, so only send is seen
+    consume: bool,
+    send: Key<T>,
+}
+
+struct OneShotMod<T> {
+    code: T,
+    // tapped twice, held until tapped again
+    locked: bool,
+    // released at this time if no key was pressed, None never times out
+    until: Option<Instant>,
+}
+
+// keys that are down that may be the start of a combo, but we don't know yet
+struct PendingCombo<T> {
+    keys: Vec<T>,
+    times: Vec<Instant>,
+}
+
+struct ActiveCombo<T> {
+    // combo keys still held down
+    held: Vec<T>,
+    // None once the combo has been released
+    code: Option<T>,
+}
+
+// a tap-hold key that is down, but we don't know yet if it is being tapped or held
+struct PendingTapHold<T> {
+    code: T,
+    tap: T,
+    hold: T,
+    since: Instant,
+    // events that came in while deciding, sent on once we know
+    buffered: Vec<(T, KeyState, Instant)>,
+}
+
+// macro steps after a delay, played from tick once it has passed so nothing waits on it
+struct PendingMacro<T> {
+    steps: VecDeque<MacroStep<T>>,
+    until: Instant,
+    // events that came in while playing, sent on once it is done
+    buffered: Vec<(T, KeyState, Instant)>,
+}
+
+enum ComposeResult<T> {
+    // typed like Key::Unicode
+    Unicode(char, char),
+    Steps(Vec<MacroStep<T>>),
+}
+
+// how unicode characters are typed, from UnicodeConfig
+enum UnicodeInput<T> {
+    // ctrl+shift+u, the hex code, then space, like GTK and IBus take it
+    CtrlShiftU,
+    // the compose key, then the sequence for the character
+    Compose {
+        key: Option<T>,
+        sequences: HashMap<char, Vec<MacroStep<T>>>,
+    },
+}
+
+impl<T: Copy> UnicodeInput<T> {
+    // adds the keys to type noshift and shift to unicode_steps, returns why any can't be typed
+    fn add_steps(
+        &self,
+        key_map: &HashMap<&'static str, T>,
+        noshift: char,
+        shift: char,
+        unicode_steps: &mut HashMap<char, Vec<MacroStep<T>>>,
+    ) -> Vec<String> {
+        let mut reasons = Vec::new();
+        for c in [noshift, shift].iter() {
+            if unicode_steps.contains_key(c) {
+                continue;
+            }
+            match self.steps(key_map, *c) {
+                Ok(steps) => {
+                    unicode_steps.insert(*c, steps);
+                }
+                Err(reason) if !reasons.contains(&reason) => reasons.push(reason),
+                Err(_) => (),
+            }
+        }
+        reasons
+    }
+
+    // the keys to type c, or why it can't be
+    fn steps(
+        &self,
+        key_map: &HashMap<&'static str, T>,
+        c: char,
+    ) -> std::result::Result<Vec<MacroStep<T>>, String> {
+        match self {
+            UnicodeInput::CtrlShiftU => {
+                let key = |name: &str| {
+                    key_map
+                        .get(name)
+                        .copied()
+                        .ok_or_else(|| format!("unicode input needs a {} key", name))
+                };
+                let (ctrl, shift, u) = (key("LCTL")?, key("LSFT")?, key("U")?);
+                let mut steps = vec![
+                    MacroStep::Down(ctrl),
+                    MacroStep::Down(shift),
+                    MacroStep::Down(u),
+                    MacroStep::Up(u),
+                    MacroStep::Up(shift),
+                    MacroStep::Up(ctrl),
+                ];
+                for digit in format!("{:X}", c as u32).chars() {
+                    let digit = key(&digit.to_string())?;
+                    steps.extend([MacroStep::Down(digit), MacroStep::Up(digit)].iter());
+                }
+                let space = key("SPC")?;
+                steps.extend([MacroStep::Down(space), MacroStep::Up(space)].iter());
+                Ok(steps)
+            }
+            UnicodeInput::Compose { key, sequences } => {
+                let key = key.ok_or("unicode.compose_key must be set to use the compose method")?;
+                let sequence = sequences.get(&c).ok_or_else(|| {
+                    format!("no unicode.compose sequence for {}", unicode_name(c))
+                })?;
+                let mut steps = vec![MacroStep::Down(key), MacroStep::Up(key)];
+                steps.extend(sequence.iter());
+                Ok(steps)
+            }
+        }
+    }
+}
+
+// why a token couldn't be parsed, token is a slice of the string being parsed so we can find where it is
+struct ParseError<'a> {
+    token: &'a str,
+    reason: String,
+}
+
+type ParseResult<'a, T> = std::result::Result<T, ParseError<'a>>;
+
+impl<'a> ParseError<'a> {
+    fn new<S: Into<String>>(token: &'a str, reason: S) -> ParseError<'a> {
+        ParseError {
+            token,
+            reason: reason.into(),
+        }
+    }
+
+    // keymap must be the string token was sliced from
+    fn in_keymap(self, keymap_index: usize, keymap: &str) -> Error {
+        let token = self.token.trim();
+        // token is a slice of keymap, so the pointer difference is where it starts
+        let offset = (token.as_ptr() as usize)
+            .saturating_sub(keymap.as_ptr() as usize)
+            .min(keymap.len());
+        let before = &keymap[..offset];
+        Error::Keymap {
+            keymap: keymap_index,
+            line: before.matches('\n').count() + 1,
+            column: before.chars().rev().take_while(|c| *c != '\n').count() + 1,
+            token: token.to_string(),
+            reason: self.reason,
+        }
+    }
+
+    // for config that isn't a keymap, where describes which part of the config it is in
+    fn in_config(self, where_: &str) -> Error {
+        Error::Config(format!(
+            "{}: {}: {}",
+            where_,
+            self.reason,
+            self.token.trim()
+        ))
+    }
 }
 
-fn parse_key<T: Clone + Copy>(key_map: &HashMap<&'static str, T>, key: &str) -> T {
-    match key_map.get(key.trim_matches(|c: char| {
+fn parse_key<'a, T: Clone + Copy + TryFrom<usize>>(
+    key_map: &HashMap<&'static str, T>,
+    key: &'a str,
+) -> ParseResult<'a, T> {
+    let name = key.trim_matches(|c: char| {
         c.is_whitespace() || c == INVERT_KEY_FLAG || c == CAPS_MODIFY_KEY_FLAG
-    })) {
-        Some(key_code) => *key_code,
-        None => panic!("unknown key: {}", key.trim()),
+    });
+    if let Some(code) = name.strip_prefix(KEY_CODE_PREFIX) {
+        // a raw code for keys without a name, like #183
+        return code
+            .parse::<usize>()
+            .ok()
+            .and_then(|code| T::try_from(code).ok())
+            .ok_or_else(|| ParseError::new(key, "invalid key code"));
+    }
+    match key_map.get(name) {
+        Some(key_code) => Ok(*key_code),
+        None => Err(ParseError::new(key, "unknown key")),
+    }
+}
+
+// split on KEY_SEPARATOR, but not inside FUNCTION_OPEN/FUNCTION_CLOSE, so TH(ESC, LCTL) stays 1 key
+fn split_keymap(keymap: &str) -> Vec<&str> {
+    let mut keys = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in keymap.char_indices() {
+        match c {
+            FUNCTION_OPEN => depth += 1,
+            FUNCTION_CLOSE if depth > 0 => depth -= 1,
+            KEY_SEPARATOR if depth == 0 => {
+                keys.push(&keymap[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    keys.push(&keymap[start..]);
+    keys
+}
+
+// NAME(arg, arg) -> (NAME, [arg, arg]), Ok(None) if key isn't a function
+fn parse_function(key: &str) -> ParseResult<'_, Option<(&str, Vec<&str>)>> {
+    let key = key.trim();
+    let open = match key.find(FUNCTION_OPEN) {
+        Some(open) => open,
+        None => return Ok(None),
+    };
+    if !key.ends_with(FUNCTION_CLOSE) {
+        return Err(ParseError::new(
+            key,
+            format!("function missing {}", FUNCTION_CLOSE),
+        ));
+    }
+    Ok(Some((
+        key[..open].trim(),
+        split_keymap(&key[open + 1..key.len() - 1]),
+    )))
+}
+
+fn parse_layer(layer: &str) -> ParseResult<'_, usize> {
+    layer
+        .trim()
+        .parse()
+        .map_err(|_| ParseError::new(layer, "layer must be a keymap index"))
+}
+
+// steps separated by KEY_SEPARATOR, KEY taps it, KEY+KEY taps them together, DOWN(KEY), UP(KEY), DELAY(ms)
+fn parse_macro<'a, T: Clone + Copy + TryFrom<usize>>(
+    key_map: &HashMap<&'static str, T>,
+    macro_steps: &'a str,
+) -> ParseResult<'a, Vec<MacroStep<T>>> {
+    let mut steps = Vec::new();
+    for step in split_keymap(macro_steps) {
+        match parse_function(step)? {
+            Some((MACRO_DOWN_FUNCTION, args)) if args.len() == 1 => {
+                steps.push(MacroStep::Down(parse_key(key_map, args[0])?))
+            }
+            Some((MACRO_UP_FUNCTION, args)) if args.len() == 1 => {
+                steps.push(MacroStep::Up(parse_key(key_map, args[0])?))
+            }
+            Some((MACRO_DELAY_FUNCTION, args)) if args.len() == 1 => {
+                steps.push(MacroStep::Delay(Duration::from_millis(
+                    args[0]
+                        .trim()
+                        .parse()
+                        .map_err(|_| ParseError::new(args[0], "delay must be milliseconds"))?,
+                )))
+            }
+            Some(_) => return Err(ParseError::new(step, "unknown macro step")),
+            None => {
+                let keys = step
+                    .split(MACRO_CHORD_SEPARATOR)
+                    .map(|k| parse_key(key_map, k))
+                    .collect::<ParseResult<Vec<T>>>()?;
+                steps.extend(keys.iter().map(|k| MacroStep::Down(*k)));
+                steps.extend(keys.iter().rev().map(|k| MacroStep::Up(*k)));
+            }
+        }
     }
+    Ok(steps)
 }
 
-fn parse_keymap_numeric<T: Clone + Copy>(
+fn parse_function_key<'a, T: Clone + Copy + TryFrom<usize>>(
     key_map: &HashMap<&'static str, T>,
-    keymap: &str,
-) -> Vec<T> {
-    keymap.split(",").map(|k| parse_key(key_map, k)).collect()
+    macro_indexes: &HashMap<&str, usize>,
+    key: &'a str,
+) -> ParseResult<'a, Key<T>> {
+    let (name, args) = match parse_function(key)? {
+        Some(function) => function,
+        None => return Err(ParseError::new(key, "not a function")),
+    };
+    Ok(match (name, args.len()) {
+        (MACRO_FUNCTION, 1) => Key::Macro(
+            *macro_indexes
+                .get(args[0].trim())
+                .ok_or_else(|| ParseError::new(args[0], "unknown macro"))?,
+        ),
+        (TAP_HOLD_FUNCTION, 2) => {
+            Key::TapHold(parse_key(key_map, args[0])?, parse_key(key_map, args[1])?)
+        }
+        (MOMENTARY_LAYER_FUNCTION, 1) => Key::Layer(LayerAction::Momentary, parse_layer(args[0])?),
+        (TOGGLE_LAYER_FUNCTION, 1) => Key::Layer(LayerAction::Toggle, parse_layer(args[0])?),
+        (ONE_SHOT_LAYER_FUNCTION, 1) => Key::Layer(LayerAction::OneShot, parse_layer(args[0])?),
+        (TO_LAYER_FUNCTION, 1) => Key::Layer(LayerAction::To, parse_layer(args[0])?),
+        _ => {
+            return Err(ParseError::new(
+                key,
+                "unknown function or wrong number of arguments",
+            ))
+        }
+    })
 }
 
-fn parse_key_half_inverted<T: Clone + Copy>(
+fn parse_key_half_inverted<'a, T: Clone + Copy + TryFrom<usize>>(
     key_map: &HashMap<&'static str, T>,
-    key: &str,
-) -> HalfInvertedKey<T> {
-    HalfInvertedKey {
-        code: parse_key(key_map, key),
+    key: &'a str,
+) -> ParseResult<'a, HalfInvertedKey<T>> {
+    Ok(HalfInvertedKey {
+        code: parse_key(key_map, key)?,
         invert_shift: key.contains(INVERT_KEY_FLAG),
         capslock_nomodify: key.contains(CAPS_MODIFY_KEY_FLAG),
+    })
+}
+
+// MOUSE_UP, or MOUSE_UP:20 to move further than the default, None if it isn't a mouse move at all
+fn parse_mouse_move(key: &str) -> Option<ParseResult<'_, (MouseMove, i32)>> {
+    let mut parts = key.splitn(2, HALF_KEY_SEPARATOR);
+    let name = parts.next()?.trim();
+    let mouse_move = MOUSE_MOVES.iter().find(|m| m.0 == name)?.1;
+    Some(match parts.next() {
+        None => Ok((mouse_move, mouse_move.default_amount())),
+        Some(amount) => match amount.trim().parse() {
+            Ok(amount) if amount > 0 => Ok((mouse_move, amount)),
+            _ => Err(ParseError::new(
+                key,
+                "mouse move amount must be a positive number",
+            )),
+        },
+    })
+}
+
+// U+00E9, or a character that isn't ASCII like é, None if key isn't a unicode character at all
+fn parse_unicode(key: &str) -> Option<ParseResult<'_, char>> {
+    let trimmed = key.trim();
+    if let Some(hex) = trimmed.strip_prefix(UNICODE_PREFIX) {
+        return Some(
+            Some(hex)
+                .filter(|hex| !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .and_then(char::from_u32)
+                .ok_or_else(|| ParseError::new(key, "invalid unicode character")),
+        );
+    }
+    let mut chars = trimmed.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if !c.is_ascii() => Some(Ok(c)),
+        _ => None,
     }
 }
 
-// maybe shortcut to this if not contains * or :
-fn parse_keymap_u16<T: Clone + Copy>(key_map: &HashMap<&'static str, T>, keymap: &str) -> Vec<T> {
-    keymap.split(",").map(|k| parse_key(key_map, k)).collect()
+// a unicode character, a pair of them split by shift like é:É, or keys written like a macro
+fn parse_compose_result<'a, T: Copy + TryFrom<usize>>(
+    key_map: &HashMap<&'static str, T>,
+    result: &'a str,
+) -> ParseResult<'a, ComposeResult<T>> {
+    let mut halves = result.splitn(2, HALF_KEY_SEPARATOR);
+    if let (Some(noshift), Some(shift)) = (halves.next(), halves.next()) {
+        if let (Some(noshift), Some(shift)) = (parse_unicode(noshift), parse_unicode(shift)) {
+            return Ok(ComposeResult::Unicode(noshift?, shift?));
+        }
+    } else if let Some(c) = parse_unicode(result) {
+        let c = c?;
+        return Ok(ComposeResult::Unicode(c, c));
+    }
+    Ok(ComposeResult::Steps(parse_macro(key_map, result)?))
 }
 
-// todo: how do I return an iterator here instead of .collect to Vec?
-fn parse_keymap<T: Copy>(key_map: &HashMap<&'static str, T>, keymap: &str) -> Vec<Key<T>> {
-    keymap
-        .split(",")
-        .map(|k| {
-            let ret: Key<T> = if k.contains(HALF_KEY_SEPARATOR) {
-                let keys: Vec<&str> = k.split(HALF_KEY_SEPARATOR).collect();
-                if keys.len() != 2 {
-                    panic!(
-                        "split key can only have 2 keys, 1 :, has {} keys",
-                        keys.len()
-                    );
-                }
-                let mut shift_half = parse_key_half_inverted(key_map, keys[1]);
-                shift_half.invert_shift = !shift_half.invert_shift;
-                Key::FullKey(parse_key_half_inverted(key_map, keys[0]), shift_half)
-            } else if k.contains(INVERT_KEY_FLAG) || k.contains(CAPS_MODIFY_KEY_FLAG) {
-                Key::HalfKey(parse_key_half_inverted(key_map, k))
-            } else {
-                Key::Direct(parse_key(key_map, k))
-            };
-            ret
-        })
-        .collect()
+// one key in a keymap that isn't the base keymap
+fn parse_keymap_key<'a, T: Copy + TryFrom<usize>>(
+    key_map: &HashMap<&'static str, T>,
+    macro_indexes: &HashMap<&str, usize>,
+    dead_key_indexes: &HashMap<&str, usize>,
+    k: &'a str,
+) -> ParseResult<'a, Key<T>> {
+    Ok(if k.contains(FUNCTION_OPEN) {
+        parse_function_key(key_map, macro_indexes, k)?
+    } else if let Some(mouse_move) = parse_mouse_move(k) {
+        let (mouse_move, amount) = mouse_move?;
+        Key::Mouse(mouse_move, amount)
+    } else if k.trim() == TRANSPARENT_KEY {
+        Key::Transparent
+    } else if k.trim().starts_with(DEAD_KEY_PREFIX) {
+        Key::Dead(
+            *dead_key_indexes
+                .get(k.trim())
+                .ok_or_else(|| ParseError::new(k, "unknown dead key"))?,
+        )
+    } else if k.contains(HALF_KEY_SEPARATOR) {
+        let keys: Vec<&str> = k.split(HALF_KEY_SEPARATOR).collect();
+        if keys.len() != 2 {
+            return Err(ParseError::new(
+                k,
+                format!(
+                    "split key can only have 2 keys, 1 {}, has {} keys",
+                    HALF_KEY_SEPARATOR,
+                    keys.len()
+                ),
+            ));
+        }
+        match (parse_unicode(keys[0]), parse_unicode(keys[1])) {
+            (Some(noshift), Some(shift)) => return Ok(Key::Unicode(noshift?, shift?)),
+            (None, None) => (),
+            _ => {
+                return Err(ParseError::new(
+                    k,
+                    "both halves of a split key must be unicode characters, or neither",
+                ))
+            }
+        }
+        let mut shift_half = parse_key_half_inverted(key_map, keys[1])?;
+        shift_half.invert_shift = !shift_half.invert_shift;
+        Key::FullKey(parse_key_half_inverted(key_map, keys[0])?, shift_half)
+    } else if let Some(c) = parse_unicode(k) {
+        let c = c?;
+        Key::Unicode(c, c)
+    } else if k.contains(INVERT_KEY_FLAG) || k.contains(CAPS_MODIFY_KEY_FLAG) {
+        Key::HalfKey(parse_key_half_inverted(key_map, k)?)
+    } else {
+        Key::Direct(parse_key(key_map, k)?)
+    })
 }
 
 impl<K, T, E, R> KeyMaps<K, T, E, R>
@@ -185,67 +673,238 @@ where
     pub fn from_cfg<P: AsRef<Path>>(
         key_map: &HashMap<&'static str, T>,
         path: P,
-    ) -> KeyMaps<K, T, E, R> {
-        let key_map_config = parse_cfg(path).expect("provided config cannot be found/parsed");
+    ) -> Result<KeyMaps<K, T, E, R>> {
+        let key_map_config = parse_cfg(path)?;
         KeyMaps::new(key_map, key_map_config)
     }
 
-    pub fn new(key_map: &HashMap<&'static str, T>, config: KeymapConfig) -> KeyMaps<K, T, E, R> {
+    /// KeyMaps for config and for each of its profiles that isn't pass-through by name, built together so errors in
+    /// any of them are all returned, and returned so they can be used instead of built again
+    pub fn with_profiles(
+        key_map: &HashMap<&'static str, T>,
+        config: &KeymapConfig,
+    ) -> Result<(Self, HashMap<String, Self>)> {
+        let mut errors = Vec::new();
+        let key_maps = KeyMaps::new(key_map, config.clone())
+            .map_err(|e| errors.push(e))
+            .ok();
+        let mut profiles = HashMap::new();
+        for name in config.profile_names() {
+            let profile_config = match config.for_profile(Some(name)) {
+                Some(profile_config) => profile_config,
+                None => continue, // pass-through
+            };
+            let in_profile = |e: Error| Error::Config(format!("profiles.{}: {}", name, e));
+            match KeyMaps::new(key_map, profile_config) {
+                Ok(profile_key_maps) => {
+                    profiles.insert(name.to_string(), profile_key_maps);
+                }
+                Err(Error::Errors(profile_errors)) => {
+                    errors.extend(profile_errors.into_iter().map(in_profile))
+                }
+                Err(e) => errors.push(in_profile(e)),
+            }
+        }
+        match (key_maps, errors.len()) {
+            (Some(key_maps), 0) => Ok((key_maps, profiles)),
+            (_, 1) => Err(errors.remove(0)),
+            _ => Err(Error::Errors(
+                errors
+                    .into_iter()
+                    .flat_map(|e| match e {
+                        Error::Errors(errors) => errors,
+                        e => vec![e],
+                    })
+                    .collect(),
+            )),
+        }
+    }
+
+    /// KeyMaps for config, its profiles are only checked by name, with_profiles builds them too
+    pub fn new(
+        key_map: &HashMap<&'static str, T>,
+        config: KeymapConfig,
+    ) -> Result<KeyMaps<K, T, E, R>> {
         if config.keymaps.len() < 2 {
-            panic!(
-                "must have at least 2 keymaps (original and mapped) but only have {},",
+            return Err(Error::Config(format!(
+                "must have at least 2 keymaps (original and mapped) but only have {}",
                 config.keymaps.len()
-            );
+            )));
         }
+        // keep going after an error so they can all be reported at once
+        let mut errors = Vec::new();
         if config.default_keymap_index >= config.keymaps.len()
             || config.revert_keymap_index >= config.keymaps.len()
         {
-            panic!("default_keymap_index ({}) and revert_keymap_index ({}) must be less than keymaps length ({}),", config.default_keymap_index, config.revert_keymap_index, config.keymaps.len());
+            errors.push(Error::Config(format!("default_keymap_index ({}) and revert_keymap_index ({}) must be less than keymaps length ({})", config.default_keymap_index, config.revert_keymap_index, config.keymaps.len())));
+        }
+        let mut base_keymap = Vec::new();
+        // String or &'static str depending on toml_serde, either derefs to &str
+        let base = &config.keymaps[0];
+        for token in split_keymap(base) {
+            let key_code = match parse_key(key_map, token) {
+                Ok(key_code) => key_code,
+                Err(e) => {
+                    errors.push(e.in_keymap(0, base));
+                    T::default()
+                }
+            };
+            // pushed even on error to keep the other keymaps lined up with this one
+            base_keymap.push(key_code);
+        }
+        let mut macros = Vec::with_capacity(config.macros.len());
+        let mut macro_names = Vec::with_capacity(config.macros.len());
+        let mut macro_indexes = HashMap::with_capacity(config.macros.len());
+        for (name, macro_steps) in config.macros.iter() {
+            macro_indexes.insert(name.as_ref(), macros.len());
+            macro_names.push(name.to_string());
+            macros.push(parse_macro(key_map, macro_steps).unwrap_or_else(|e| {
+                errors.push(e.in_config(&format!("macros.{}", name)));
+                Vec::new()
+            }));
+        }
+        let unicode_input = match config.unicode.method {
+            UnicodeMethod::CtrlShiftU => UnicodeInput::CtrlShiftU,
+            UnicodeMethod::Compose => {
+                let key = config.unicode.compose_key.as_ref().and_then(|key| {
+                    parse_key(key_map, key)
+                        .map_err(|e| errors.push(e.in_config("unicode.compose_key")))
+                        .ok()
+                });
+                let mut sequences = HashMap::with_capacity(config.unicode.compose.len());
+                for (c, sequence) in config.unicode.compose.iter() {
+                    let parsed = parse_unicode(c)
+                        .unwrap_or_else(|| Err(ParseError::new(c, "invalid unicode character")))
+                        .and_then(|c| Ok((c, parse_macro(key_map, sequence)?)));
+                    match parsed {
+                        Ok((c, steps)) => {
+                            sequences.insert(c, steps);
+                        }
+                        Err(e) => errors.push(e.in_config("unicode.compose")),
+                    }
+                }
+                UnicodeInput::Compose { key, sequences }
+            }
+        };
+        let mut unicode_steps = HashMap::new();
+
+        let mut dead_keys = Vec::with_capacity(config.compose.len());
+        let mut dead_key_names = Vec::with_capacity(config.compose.len());
+        let mut dead_key_indexes = HashMap::with_capacity(config.compose.len());
+        for (name, sequences) in config.compose.iter() {
+            let where_ = format!("compose.{}", name);
+            if !name.starts_with(DEAD_KEY_PREFIX) {
+                errors.push(Error::Config(format!(
+                    "{}: dead key names must start with {}",
+                    where_, DEAD_KEY_PREFIX
+                )));
+            }
+            dead_key_indexes.insert(name.as_ref(), dead_keys.len());
+            dead_key_names.push(name.to_string());
+            let mut dead_key = Vec::with_capacity(sequences.len());
+            for (sequence, result) in sequences.iter() {
+                let keys = split_keymap(sequence)
+                    .into_iter()
+                    .map(|k| parse_key(key_map, k))
+                    .collect::<ParseResult<Vec<T>>>();
+                match (keys, parse_compose_result(key_map, result)) {
+                    (Ok(keys), Ok(composed)) => {
+                        if let ComposeResult::Unicode(noshift, shift) = composed {
+                            for reason in
+                                unicode_input.add_steps(key_map, noshift, shift, &mut unicode_steps)
+                            {
+                                errors.push(ParseError::new(result, reason).in_config(&where_));
+                            }
+                        }
+                        dead_key.push((keys, composed));
+                    }
+                    (keys, composed) => errors.extend(
+                        keys.err()
+                            .into_iter()
+                            .chain(composed.err())
+                            .map(|e| e.in_config(&where_)),
+                    ),
+                }
+            }
+            dead_keys.push(dead_key);
         }
-        let base_keymap = parse_keymap_numeric(key_map, &config.keymaps[0]);
         //println!("base_keymap      : {:?}", base_keymap);
         let mut keymaps: Vec<Box<dyn KeyMapper<K, T, E, R>>> = vec![Box::new(Key::Noop)]; // todo: can we share the box?
         let mut keymap_index_keys: HashMap<T, usize> = HashMap::new();
         for (x, v) in config.keymaps.iter().enumerate() {
-            keymap_index_keys.insert(*key_map.get(&*x.to_string()).unwrap(), x);
+            if let Some(key) = key_map.get(&*x.to_string()) {
+                keymap_index_keys.insert(*key, x);
+            }
             if x == 0 {
                 continue;
             }
+            let mut tokens = split_keymap(v);
+            if tokens.len() > base_keymap.len() {
+                errors.push(
+                    ParseError::new(
+                        tokens[base_keymap.len()],
+                        format!(
+                            "all keymaps must be the same length, keymap index 0 length: {}, index {} length: {}",
+                            base_keymap.len(),
+                            x,
+                            tokens.len()
+                        ),
+                    )
+                    .in_keymap(x, v),
+                );
+                tokens.truncate(base_keymap.len());
+            }
             if v.contains(HALF_KEY_SEPARATOR)
                 || v.contains(INVERT_KEY_FLAG)
                 || v.contains(CAPS_MODIFY_KEY_FLAG)
+                || v.contains(FUNCTION_OPEN)
+                || tokens.iter().any(|k| {
+                    k.trim() == TRANSPARENT_KEY
+                        || parse_mouse_move(k).is_some()
+                        || parse_unicode(k).is_some()
+                        || k.trim().starts_with(DEAD_KEY_PREFIX)
+                })
             {
-                // we need KeyMap, the complicated more memory taking one
-                let v = parse_keymap(key_map, v);
+                // we need KeyMap, the complicated one
                 let mut keymap = KeyMap::new();
-                let mut i: usize = 0;
-                for key_code in v {
-                    // if it's a direct key and it's the same, don't do any mapping
-                    if let Key::Direct(key) = key_code {
-                        if base_keymap[i] != key {
-                            keymap.map(base_keymap[i], key_code);
+                for (i, token) in tokens.into_iter().enumerate() {
+                    match parse_keymap_key(key_map, &macro_indexes, &dead_key_indexes, token) {
+                        Ok(Key::Layer(_, layer)) if layer >= config.keymaps.len() => errors.push(
+                            ParseError::new(
+                                token,
+                                format!(
+                                    "layer ({}) must be less than keymaps length ({})",
+                                    layer,
+                                    config.keymaps.len()
+                                ),
+                            )
+                            .in_keymap(x, v),
+                        ),
+                        // if it's a direct key and it's the same, don't do any mapping
+                        Ok(Key::Direct(key)) if base_keymap[i] == key => (),
+                        Ok(Key::Unicode(noshift, shift)) => {
+                            for reason in
+                                unicode_input.add_steps(key_map, noshift, shift, &mut unicode_steps)
+                            {
+                                errors.push(ParseError::new(token, reason).in_keymap(x, v));
+                            }
+                            keymap.map(base_keymap[i], Key::Unicode(noshift, shift))
                         }
-                    } else {
-                        keymap.map(base_keymap[i], key_code);
-                    }
-                    i = i + 1;
-                    if i > base_keymap.len() {
-                        panic!("all keymaps must be the same length, keymap index 0 length: {}, index {} length: {},", base_keymap.len(), x, i);
+                        Ok(key_code) => keymap.map(base_keymap[i], key_code),
+                        Err(e) => errors.push(e.in_keymap(x, v)),
                     }
                 }
                 keymaps.push(Box::new(keymap));
             } else {
                 // this is a simple keymap
-                let v = parse_keymap_u16(key_map, v);
                 let mut keymap = CodeKeyMap::new();
-                let mut i: usize = 0;
-                for key_code in v {
-                    if base_keymap[i] != key_code {
-                        keymap.map(base_keymap[i], key_code);
-                    }
-                    i = i + 1;
-                    if i > base_keymap.len() {
-                        panic!("all keymaps must be the same length, keymap index 0 length: {}, index {} length: {},", base_keymap.len(), x, i);
+                for (i, token) in tokens.into_iter().enumerate() {
+                    match parse_key(key_map, token) {
+                        Ok(key_code) if base_keymap[i] != key_code => {
+                            keymap.map(base_keymap[i], key_code)
+                        }
+                        Ok(_) => (),
+                        Err(e) => errors.push(e.in_keymap(x, v)),
                     }
                 }
                 keymaps.push(Box::new(keymap));
@@ -255,225 +914,1500 @@ where
         //println!("keymap_index_keys: {:?}", keymap_index_keys);
 
         let mut revert_default_keys = Vec::new();
-        if config.revert_default_key.is_some() {
-            revert_default_keys.push(parse_key(key_map, &config.revert_default_key.unwrap()));
+        if let Some(revert_default_key) = &config.revert_default_key {
+            match parse_key(key_map, revert_default_key) {
+                Ok(key_code) => revert_default_keys.push(key_code),
+                Err(e) => errors.push(e.in_config("revert_default_key")),
+            }
         }
-        if config.revert_default_keys.is_some() {
-            for revert_default_key in config.revert_default_keys.unwrap() {
-                let revert_default_key = parse_key(key_map, &revert_default_key);
-                if !revert_default_keys.contains(&revert_default_key) {
-                    revert_default_keys.push(revert_default_key);
+        if let Some(config_revert_default_keys) = &config.revert_default_keys {
+            for revert_default_key in config_revert_default_keys {
+                match parse_key(key_map, revert_default_key) {
+                    Ok(key_code) if !revert_default_keys.contains(&key_code) => {
+                        revert_default_keys.push(key_code)
+                    }
+                    Ok(_) => (),
+                    Err(e) => errors.push(e.in_config("revert_default_keys")),
                 }
             }
         }
         // revert_default_keys may be empty, but that's ok
 
-        KeyMaps {
-            keymaps: keymaps,
-            keymap_index_keys: keymap_index_keys,
-            switch_layout_keys: config
-                .switch_layout_keys
-                .iter()
-                .map(|k| parse_key(key_map, k).into())
-                .collect(),
-            key_state: [false; KEY_MAX],
-            // todo: detect key state? at least CAPSLOCK...
-            revert_default_keys,
-            revert_keymap_index: config.revert_keymap_index,
-            chosen_keymap_index: config.default_keymap_index,
-            current_keymap_index: config.default_keymap_index,
-            devices: config.devices,
+        let mut switch_layout_keys = Vec::with_capacity(config.switch_layout_keys.len());
+        for switch_layout_key in config.switch_layout_keys.iter() {
+            match parse_key(key_map, switch_layout_key) {
+                Ok(key_code) => switch_layout_keys.push(key_code.into()),
+                Err(e) => errors.push(e.in_config("switch_layout_keys")),
+            }
         }
-    }
-    //}
 
-    //impl KeyMapper for KeyMaps {
-    //impl KeyMaps {
-    pub fn send_event(&mut self, mut event: &mut E, device: &K) -> Result<R> {
-        let value = event.value();
-        if value != KeyState::OTHER {
-            // todo: index check here...
-            if event.code() == device.caps_lock_code() {
-                if value == KeyState::DOWN {
-                    self.key_state[device.caps_lock_code().into()] =
-                        !self.key_state[device.caps_lock_code().into()];
-                }
-            } else {
-                let idx = event.code().into();
-                if idx >= KEY_MAX {
-                    // oh well, send it directly then
-                    return device.send(event);
+        let scroll_button = match &config.scroll_button {
+            Some(scroll_button) => match parse_key(key_map, scroll_button) {
+                Ok(key_code) => Some(key_code),
+                Err(e) => {
+                    errors.push(e.in_config("scroll_button"));
+                    None
                 }
-                self.key_state[idx] = value == KeyState::DOWN;
+            },
+            None => None,
+        };
+
+        let repeat = config.repeat.as_ref().map(|repeat| {
+            if repeat.period_ms == 0 {
+                errors.push(Error::Config(
+                    "repeat.period_ms must be more than 0".to_string(),
+                ));
             }
-            let mut switch_layout_keys_pressed = true;
-            for layout_switch_key in self.switch_layout_keys.iter_mut() {
-                if !self.key_state[*layout_switch_key] {
-                    switch_layout_keys_pressed = false;
-                    break;
+            let mut no_repeat = Vec::with_capacity(repeat.no_repeat.len());
+            for key in repeat.no_repeat.iter() {
+                match parse_key(key_map, key) {
+                    Ok(key_code) => no_repeat.push(key_code),
+                    Err(e) => errors.push(e.in_config("repeat.no_repeat")),
                 }
             }
-            //println!("switch_layout_keys_pressed: {}", self.switch_layout_keys_pressed);
-            if switch_layout_keys_pressed {
-                let new_index = self.keymap_index_keys.get(&event.code());
-                if new_index.is_some() {
-                    self.chosen_keymap_index = *new_index.unwrap();
-                    self.current_keymap_index = self.chosen_keymap_index; // todo: what if revert_default_key is held? for now ignore
-                    return device.block_key(); // we don't want to also send this keypress, so bail
+            Repeat {
+                delay: Duration::from_millis(repeat.delay_ms),
+                period: Duration::from_millis(repeat.period_ms),
+                no_repeat,
+            }
+        });
+
+        let mut one_shot_mods = HashMap::with_capacity(config.one_shot.len());
+        for (key, timeout_ms) in config.one_shot.iter() {
+            match parse_key(key_map, key) {
+                Ok(key_code) => {
+                    one_shot_mods.insert(
+                        key_code,
+                        Some(*timeout_ms)
+                            .filter(|ms| *ms != 0)
+                            .map(Duration::from_millis),
+                    );
                 }
+                Err(e) => errors.push(e.in_config("one_shot")),
             }
-            if self.revert_default_keys.contains(&event.code()) {
-                match value {
-                    KeyState::DOWN => {
-                        // todo: should we release currently held keys and then press them back down here, kinda the opposite of below? not for now...
-                        self.current_keymap_index = self.revert_keymap_index
-                    }
-                    KeyState::UP => {
-                        self.current_keymap_index = self.chosen_keymap_index;
-                        #[cfg(not(target_os = "macos"))]
+        }
+
+        let modifiers: Vec<T> = MODIFIER_GROUPS
+            .iter()
+            .flat_map(|(_, names)| names.iter())
+            .filter_map(|name| key_map.get(name).copied())
+            .collect();
+        let mut mod_rules = Vec::with_capacity(config.mod_rules.len());
+        for (i, rule) in config.mod_rules.iter().enumerate() {
+            let ModRuleConfig {
+                key,
+                mods,
+                send,
+                only,
+                consume,
+            } = rule;
+            let key =
+                parse_key(key_map, key).map_err(|e| e.in_config(&format!("mod_rules[{}].key", i)));
+            let mods = mods
+                .iter()
+                .map(
+                    |m| match MODIFIER_GROUPS.iter().find(|(group, _)| m.trim() == *group) {
+                        Some((_, names)) => Ok(names
+                            .iter()
+                            .filter_map(|name| key_map.get(name).copied())
+                            .collect()),
+                        None => parse_key(key_map, m).map(|code| vec![code]),
+                    },
+                )
+                .collect::<ParseResult<_>>()
+                .map_err(|e| e.in_config(&format!("mod_rules[{}].mods", i)));
+            let send_key = parse_keymap_key(key_map, &macro_indexes, &dead_key_indexes, send)
+                .and_then(|send_key| match send_key {
+                    Key::TapHold(_, _) | Key::Dead(_) | Key::Transparent => Err(ParseError::new(
+                        send,
+                        "can't be a tap-hold, dead or transparent key",
+                    )),
+                    Key::Layer(_, layer) if layer >= config.keymaps.len() => Err(ParseError::new(
+                        send,
+                        format!(
+                            "layer ({}) must be less than keymaps length ({})",
+                            layer,
+                            config.keymaps.len()
+                        ),
+                    )),
+                    Key::Unicode(noshift, shift) => {
+                        match unicode_input
+                            .add_steps(key_map, noshift, shift, &mut unicode_steps)
+                            .pop()
                         {
-                            // need to release all currently held down keys, except this one, otherwise ctrl+c will get c stuck because code c value 1 will be sent, but then we'll let go of ctrl, and code j value 0 is sent, so c is never released
-                            let orig_code = event.code();
-                            for (idx, key_down) in self.key_state.iter_mut().enumerate() {
-                                if *key_down {
-                                    device.send_mod_code_value(
-                                        T::try_from(idx).unwrap_or_else(|_| {
-                                            panic!("cannot convert from usize to T ????")
-                                        }),
-                                        true,
-                                        event,
-                                    )?;
-                                    *key_down = false;
-                                }
-                            }
-                            // todo: seems like we should not send this here, and instead just set the original code back, and pass it through the keymaps?
-                            return device.send_mod_code_value(orig_code, true, event);
+                            Some(reason) => Err(ParseError::new(send, reason)),
+                            None => Ok(send_key),
                         }
                     }
-                    _ => (), // do nothing for 2
+                    send_key => Ok(send_key),
+                })
+                .map_err(|e| e.in_config(&format!("mod_rules[{}].send", i)));
+            match (key, mods, send_key) {
+                (Ok(key), Ok(mods), Ok(send)) => mod_rules.push(ModRule {
+                    key,
+                    mods,
+                    only: *only,
+                    consume: *consume,
+                    send,
+                }),
+                (key, mods, send) => {
+                    errors.extend(key.err().into_iter().chain(mods.err()).chain(send.err()))
                 }
             }
         }
-        self.keymaps[self.current_keymap_index].send_event(&self.key_state, &mut event, device)
-    }
-}
-
-// 249 is one more than KEY_MICMUTE which is max key in uinput-sys event.rs
-const KEY_MAX: usize = 249;
 
-struct KeyMap<T: Into<usize> + Copy> {
-    keymap: [Key<T>; KEY_MAX],
-}
+        let mut combos = Vec::with_capacity(config.combos.len());
+        for (
+            i,
+            ComboConfig {
+                keys,
+                send,
+                term_ms,
+            },
+        ) in config.combos.iter().enumerate()
+        {
+            if keys.len() < 2 {
+                errors.push(Error::Config(format!(
+                    "combos[{}]: combos must have at least 2 keys: {:?}",
+                    i, keys
+                )));
+            }
+            let keys = keys
+                .iter()
+                .map(|k| parse_key(key_map, k))
+                .collect::<ParseResult<_>>()
+                .map_err(|e| e.in_config(&format!("combos[{}].keys", i)));
+            let code =
+                parse_key(key_map, send).map_err(|e| e.in_config(&format!("combos[{}].send", i)));
+            match (keys, code) {
+                (Ok(keys), Ok(code)) => combos.push(Combo {
+                    keys,
+                    code,
+                    term: Duration::from_millis(*term_ms),
+                }),
+                (keys, code) => errors.extend(keys.err().into_iter().chain(code.err())),
+            }
+        }
 
-impl<T: Into<usize> + Copy> KeyMap<T> {
-    pub fn new() -> Self {
-        KeyMap {
-            keymap: [Key::Noop; KEY_MAX],
+        for (list, matchers) in [
+            ("always_grab", &config.devices.always_grab),
+            ("grab", &config.devices.grab),
+        ] {
+            for (i, matcher) in matchers.iter().enumerate() {
+                if let Some(profile) = &matcher.profile {
+                    if !config.profiles.contains_key(profile.as_str()) {
+                        errors.push(Error::Config(format!(
+                            "devices.{}[{}].profile: unknown profile: {}",
+                            list, i, profile
+                        )));
+                    }
+                }
+            }
+        }
+        match errors.len() {
+            0 => (),
+            1 => return Err(errors.remove(0)),
+            _ => return Err(Error::Errors(errors)),
         }
-    }
 
-    pub fn map(&mut self, from: T, to: Key<T>) {
-        self.keymap[from.into()] = to;
+        Ok(KeyMaps {
+            keymaps,
+            base_keymap,
+            macro_names,
+            keymap_index_keys,
+            switch_layout_keys,
+            // the platform can seed this from the real keyboard with seed_key_state
+            key_state: HashSet::new(),
+            revert_default_keys,
+            revert_keymap_index: config.revert_keymap_index,
+            tapping_term: Duration::from_millis(config.tap_hold.tapping_term_ms),
+            permissive_hold: config.tap_hold.permissive_hold,
+            hold_on_other_key_press: config.tap_hold.hold_on_other_key_press,
+            macros,
+            unicode_steps,
+            dead_keys,
+            dead_key_names,
+            combos,
+            scroll_button,
+            repeat,
+            one_shot_mods,
+            mod_rules,
+            modifiers,
+            chosen_keymap_index: config.default_keymap_index,
+            reverting: false,
+            layers: Vec::new(),
+            one_shot_layer: None,
+            pressed_on: HashMap::new(),
+            half_key_codes: HashMap::new(),
+            combo_pending: None,
+            active_combos: Vec::new(),
+            tap_hold_pending: None,
+            tap_hold_down: HashMap::new(),
+            repeating: None,
+            one_shot_pending: Vec::new(),
+            one_shot_down: HashMap::new(),
+            one_shot_deferred: false,
+            compose_pending: None,
+            compose_swallowed: Vec::new(),
+            mod_rules_down: HashMap::new(),
+            macro_pending: None,
+            devices: config.devices,
+        })
     }
-}
 
-impl<K, T, E, R> KeyMapper<K, T, E, R> for KeyMap<T>
-where
-    T: Into<usize> + Copy,
-    E: KeyEvent<T>,
-    K: Keyboard<T, E, R>,
-{
-    fn send_event(&self, key_state: &[bool], event: &mut E, device: &K) -> Result<R> {
-        self.keymap[event.code().into()].send_event(key_state, event, device)
+    /// Button that turns pointer movement into scrolling while held, handled by the platform since KeyMaps only sees keys
+    pub fn scroll_button(&self) -> Option<T> {
+        self.scroll_button
     }
-}
 
-struct CodeKeyMap<T: Into<usize> + TryFrom<usize> + Copy + Default> {
-    keymap: [T; KEY_MAX],
-}
+    /// Start from the keys the real keyboard has held down and its caps lock, instead of nothing held and caps lock off
+    pub fn seed_key_state(&mut self, held: &[T], caps_lock: bool, device: &K) {
+        self.key_state = held
+            .iter()
+            .filter(|code| **code != device.caps_lock_code())
+            .map(|code| (*code).into())
+            .collect();
+        self.set_caps_lock(caps_lock, device);
+    }
 
-impl<T: Into<usize> + TryFrom<usize> + Copy + Default> CodeKeyMap<T> {
-    pub fn new() -> Self {
-        let mut keymap = [T::default(); KEY_MAX];
-        for (x, v) in keymap.iter_mut().enumerate() {
-            *v = T::try_from(x).unwrap_or_else(|_| panic!("cannot convert from usize to T ????"));
+    /// Whether caps lock is on for real, like from the keyboard's LED, instead of counting caps lock presses
+    pub fn set_caps_lock(&mut self, on: bool, device: &K) {
+        let caps_lock = device.caps_lock_code().into();
+        if on {
+            self.key_state.insert(caps_lock);
+        } else {
+            self.key_state.remove(&caps_lock);
         }
-        //println!("keymap: {:?}", &keymap[..]);
-        CodeKeyMap { keymap }
     }
 
-    pub fn map(&mut self, from: T, to: T) {
-        self.keymap[from.into()] = to;
+    /// How many keymaps there are, including keymap 0
+    pub fn keymaps_len(&self) -> usize {
+        self.keymaps.len()
     }
-}
 
-impl<K, T, E, R> KeyMapper<K, T, E, R> for CodeKeyMap<T>
-where
-    T: Into<usize> + TryFrom<usize> + Copy + Default,
-    E: KeyEvent<T>,
-    K: Keyboard<T, E, R>,
-{
-    fn send_event(&self, _key_state: &[bool], event: &mut E, device: &K) -> Result<R> {
-        device.send_mod_code(self.keymap[event.code().into()], event)
-        //self.keymap[event.code().into()].send_event(key_state, event, device)
+    /// Every key keymap index changes as (base key, mapped key written like it is in a keymap, flags spelled out)
+    pub fn describe_keymap(
+        &self,
+        key_map: &HashMap<&'static str, T>,
+        index: usize,
+    ) -> Vec<(String, String, String)> {
+        let names = key_names(key_map);
+        self.base_keymap
+            .iter()
+            .filter_map(|code| match self.keymaps[index].key(*code) {
+                None | Some(Key::Noop) => None,
+                Some(Key::Direct(mapped)) if mapped == *code => None,
+                Some(key) => {
+                    let (mapped, flags) =
+                        key.describe(&names, &self.macro_names, &self.dead_key_names);
+                    Some((key_name(&names, *code), mapped, flags))
+                }
+            })
+            .collect()
     }
-}
+    //}
 
-// todo:capslock_nomodify is like a whole-key thing, not a half-key thing, split code/invert_shift to own struct, send into send_key from *InvertedKey, maybe anyway, consider it, maybe 1 char for whole key and another for half?
-#[derive(Clone, Copy)]
-pub struct HalfInvertedKey<T: Clone + Copy> {
-    pub code: T,
-    // code this is describing
-    pub invert_shift: bool,
-    // true to invert shift for this code
-    pub capslock_nomodify: bool,
-    // true means capslock does not normally modify this, but you would like it to
-}
+    //impl KeyMapper for KeyMaps {
+    //impl KeyMaps {
+    pub fn send_event(&mut self, event: &mut E, device: &K) -> Result<R> {
+        self.send_event_at(event, device, Instant::now())
+    }
 
-impl<K, T, E, R> KeyMapper<K, T, E, R> for HalfInvertedKey<T>
-where
-    T: Into<usize> + Clone + Copy,
-    E: KeyEvent<T>,
-    K: Keyboard<T, E, R>,
-{
-    fn send_event(&self, key_state: &[bool], event: &mut E, device: &K) -> Result<R> {
-        let left_shift = key_state[device.left_shift_code().into()];
-        let right_shift = key_state[device.right_shift_code().into()];
-        let caps_lock = key_state[device.caps_lock_code().into()];
-        device.send_half_inverted_key(self, event, left_shift, right_shift, caps_lock)
+    /// How long until tick() needs called, None means nothing is waiting on time to pass
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout_at(Instant::now())
+    }
+
+    /// Call this when timeout() has passed without an event coming in, event is only used as a template to send keys
+    pub fn tick(&mut self, event: &mut E, device: &K) -> Result<()> {
+        self.tick_at(event, device, Instant::now())
+    }
+
+    fn timeout_at(&self, now: Instant) -> Option<Duration> {
+        let tap_hold = self
+            .tap_hold_pending
+            .as_ref()
+            .map(|pending| pending.since + self.tapping_term);
+        let combo = self.combo_deadline();
+        let repeat = self.repeating.map(|(_, next)| next);
+        let one_shot = self
+            .one_shot_pending
+            .iter()
+            .filter(|one_shot| !one_shot.locked && !self.one_shot_deferred)
+            .filter_map(|one_shot| one_shot.until)
+            .min();
+        let macro_steps = self.macro_pending.as_ref().map(|pending| pending.until);
+        [tap_hold, combo, repeat, one_shot, macro_steps]
+            .iter()
+            .flatten()
+            .min()
+            .map(|deadline| deadline.saturating_duration_since(now))
+    }
+
+    fn tick_at(&mut self, event: &mut E, device: &K, now: Instant) -> Result<()> {
+        if self
+            .macro_pending
+            .as_ref()
+            .map_or(false, |pending| pending.until <= now)
+        {
+            self.resume_macro(event, device, now)?;
+        }
+        if self
+            .combo_deadline()
+            .map_or(false, |deadline| deadline <= now)
+        {
+            self.resolve_combo(event, device, now)?;
+        }
+        if let Some(pending) = &self.tap_hold_pending {
+            if pending.since + self.tapping_term <= now {
+                self.resolve_tap_hold(true, event, device, now)?;
+            }
+        }
+        if let (Some(repeat), Some((code, next))) = (&self.repeat, self.repeating) {
+            if next <= now {
+                self.repeating = Some((code, now + repeat.period));
+                let (orig_code, orig_value) = (event.code(), event.value());
+                event.set_code(code);
+                event.set_value(KeyState::OTHER);
+                self.key_event_at(event, device, now)?;
+                // nothing from the keyboard follows to synchronize this one
+                device.synchronize()?;
+                event.set_code(orig_code);
+                event.set_value(orig_value);
+            }
+        }
+        if !self.one_shot_deferred && self.release_one_shot(Some(now), event, device, now)? {
+            device.synchronize()?;
+        }
+        Ok(())
+    }
+
+    // one-shot modifiers that aren't locked or physically held are released, only those timed out by if it is given, true if any were
+    fn release_one_shot(
+        &mut self,
+        timed_out_by: Option<Instant>,
+        event: &mut E,
+        device: &K,
+        now: Instant,
+    ) -> Result<bool> {
+        let (release, keep): (Vec<_>, Vec<_>) = std::mem::take(&mut self.one_shot_pending)
+            .into_iter()
+            .partition(|one_shot| {
+                !one_shot.locked
+                    && !self.one_shot_down.contains_key(&one_shot.code)
+                    && timed_out_by
+                        .map_or(true, |at| one_shot.until.map_or(false, |until| until <= at))
+            });
+        self.one_shot_pending = keep;
+        if release.is_empty() {
+            return Ok(false);
+        }
+        let (orig_code, orig_value) = (event.code(), event.value());
+        for one_shot in release {
+            event.set_code(one_shot.code);
+            event.set_value(KeyState::UP);
+            self.combo_event_at(event, device, now)?;
+        }
+        event.set_code(orig_code);
+        event.set_value(orig_value);
+        Ok(true)
+    }
+
+    // releases the one-shot modifiers deferred for the key pressed after them, once that key is no longer held back
+    fn release_deferred_one_shot(&mut self, event: &mut E, device: &K, now: Instant) -> Result<()> {
+        if self.one_shot_deferred && self.combo_pending.is_none() && self.tap_hold_pending.is_none()
+        {
+            self.one_shot_deferred = false;
+            self.release_one_shot(None, event, device, now)?;
+        }
+        Ok(())
+    }
+
+    // a one-shot modifier tapped stays down for the next key, tapped twice stays down until tapped again
+    fn one_shot_event_at(&mut self, event: &mut E, device: &K, now: Instant) -> Result<R> {
+        let code = event.code();
+        let pending = self
+            .one_shot_pending
+            .iter()
+            .position(|one_shot| one_shot.code == code);
+        match event.value() {
+            KeyState::DOWN => {
+                self.one_shot_down.insert(code, false);
+                if pending.is_some() {
+                    // still down from when it was tapped
+                    return device.block_key();
+                }
+                self.combo_event_at(event, device, now)
+            }
+            KeyState::UP => {
+                let interrupted = self.one_shot_down.remove(&code).unwrap_or(true);
+                match pending {
+                    Some(pos) if !interrupted && !self.one_shot_pending[pos].locked => {
+                        self.one_shot_pending[pos].locked = true;
+                        device.block_key()
+                    }
+                    Some(pos) => {
+                        self.one_shot_pending.remove(pos);
+                        self.combo_event_at(event, device, now)
+                    }
+                    None if !interrupted => {
+                        self.one_shot_pending.push(OneShotMod {
+                            code,
+                            locked: false,
+                            until: self.one_shot_mods[&code].map(|timeout| now + timeout),
+                        });
+                        device.block_key()
+                    }
+                    None => self.combo_event_at(event, device, now),
+                }
+            }
+            KeyState::OTHER if pending.is_some() => device.block_key(),
+            KeyState::OTHER => self.combo_event_at(event, device, now),
+        }
+    }
+
+    // combos that could still be completed by adding keys to the ones pressed so far
+    fn matching_combos<'a>(&'a self, keys: &'a [T]) -> impl Iterator<Item = &'a Combo<T>> + 'a {
+        self.combos
+            .iter()
+            .filter(move |combo| keys.iter().all(|k| combo.keys.contains(k)))
+    }
+
+    // the longest term of any combo the pending keys could still become
+    fn combo_deadline(&self) -> Option<Instant> {
+        let pending = self.combo_pending.as_ref()?;
+        self.matching_combos(&pending.keys)
+            .map(|combo| combo.term)
+            .max()
+            .map(|term| pending.times[0] + term)
+    }
+
+    // sends the combo the pending keys complete, or if they don't complete one, sends the keys on as pressed
+    fn resolve_combo(&mut self, event: &mut E, device: &K, now: Instant) -> Result<()> {
+        let pending = match self.combo_pending.take() {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+        let complete = self
+            .matching_combos(&pending.keys)
+            .find(|combo| combo.keys.len() == pending.keys.len())
+            .map(|combo| combo.code);
+        if let Some(code) = complete {
+            device.send_mod_code_value(code, false, event)?;
+            device.synchronize()?;
+            self.active_combos.push(ActiveCombo {
+                held: pending.keys,
+                code: Some(code),
+            });
+            self.release_deferred_one_shot(event, device, now)?;
+        } else {
+            let (orig_code, orig_value) = (event.code(), event.value());
+            for (code, time) in pending.keys.into_iter().zip(pending.times) {
+                event.set_code(code);
+                event.set_value(KeyState::DOWN);
+                self.tap_hold_event_at(event, device, time)?;
+                self.release_deferred_one_shot(event, device, time)?;
+            }
+            event.set_code(orig_code);
+            event.set_value(orig_value);
+        }
+        Ok(())
+    }
+
+    fn send_event_at(&mut self, event: &mut E, device: &K, now: Instant) -> Result<R> {
+        if let Some(pending) = &mut self.macro_pending {
+            // keys pressed while a macro plays come after it
+            if event.value() != KeyState::OTHER {
+                pending.buffered.push((event.code(), event.value(), now));
+            }
+            return device.block_key();
+        }
+        // without tick our own never come, so the keyboard's are passed on
+        if let Some(repeat) = self.repeat.as_ref().filter(|_| device.drives_tick()) {
+            let code = event.code();
+            match event.value() {
+                // we send our own from tick
+                KeyState::OTHER => return device.block_key(),
+                KeyState::DOWN if !repeat.no_repeat.contains(&code) => {
+                    self.repeating = Some((code, now + repeat.delay))
+                }
+                KeyState::UP if self.repeating.map_or(false, |(c, _)| c == code) => {
+                    self.repeating = None
+                }
+                _ => (),
+            }
+        }
+        self.key_event_at(event, device, now)
+    }
+
+    // one-shot modifiers, then on to combos, for events from the keyboard and the auto-repeats tick sends alike
+    fn key_event_at(&mut self, event: &mut E, device: &K, now: Instant) -> Result<R> {
+        let code = event.code();
+        if self.one_shot_mods.contains_key(&code) {
+            return self.one_shot_event_at(event, device, now);
+        }
+        if event.value() == KeyState::DOWN
+            && !(self.one_shot_down.is_empty() && self.one_shot_pending.is_empty())
+        {
+            // the next key after a one-shot modifier was tapped, then the modifier is released, once the key is sent if
+            // a combo or tap-hold key holds it back
+            for interrupted in self.one_shot_down.values_mut() {
+                *interrupted = true;
+            }
+            self.one_shot_deferred = true;
+            let ret = self.combo_event_at(event, device, now)?;
+            self.release_deferred_one_shot(event, device, now)?;
+            return Ok(ret);
+        }
+        self.combo_event_at(event, device, now)
+    }
+
+    fn combo_event_at(&mut self, event: &mut E, device: &K, now: Instant) -> Result<R> {
+        let code = event.code();
+        let value = event.value();
+        if let Some(pos) = self
+            .active_combos
+            .iter()
+            .position(|combo| combo.held.contains(&code))
+        {
+            // a key of a combo we sent, the combo is released with the first key, but swallow them all
+            if value == KeyState::UP {
+                let combo = &mut self.active_combos[pos];
+                combo.held.retain(|k| *k != code);
+                if let Some(combo_code) = combo.code.take() {
+                    device.send_mod_code_value(combo_code, true, event)?;
+                }
+                if combo.held.is_empty() {
+                    self.active_combos.remove(pos);
+                }
+            }
+            return device.block_key();
+        }
+        if let Some(pending) = &self.combo_pending {
+            if self
+                .combo_deadline()
+                .map_or(false, |deadline| deadline > now)
+            {
+                if value == KeyState::OTHER {
+                    // auto-repeat while we are still deciding
+                    return device.block_key();
+                }
+                if value == KeyState::DOWN && !pending.keys.contains(&code) {
+                    let mut keys = pending.keys.clone();
+                    keys.push(code);
+                    let (matching, done) =
+                        self.matching_combos(&keys)
+                            .fold((false, true), |(_, done), combo| {
+                                // complete, and no bigger combo to wait for
+                                (true, done && combo.keys.len() == keys.len())
+                            });
+                    if matching {
+                        if let Some(pending) = &mut self.combo_pending {
+                            pending.keys.push(code);
+                            pending.times.push(now);
+                        }
+                        if done {
+                            self.resolve_combo(event, device, now)?;
+                        }
+                        return device.block_key();
+                    }
+                }
+            }
+            // timed out, released, or a key that isn't part of any combo with these
+            self.resolve_combo(event, device, now)?;
+            return self.combo_event_at(event, device, now);
+        }
+        if value == KeyState::DOWN && self.combos.iter().any(|combo| combo.keys.contains(&code)) {
+            self.combo_pending = Some(PendingCombo {
+                keys: vec![code],
+                times: vec![now],
+            });
+            return device.block_key();
+        }
+        self.tap_hold_event_at(event, device, now)
+    }
+
+    // sends the tap or hold key for the pending tap-hold key, then replays anything buffered while we waited to decide
+    fn resolve_tap_hold(
+        &mut self,
+        hold: bool,
+        event: &mut E,
+        device: &K,
+        now: Instant,
+    ) -> Result<()> {
+        let pending = match self.tap_hold_pending.take() {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+        let (orig_code, orig_value) = (event.code(), event.value());
+        let code = if hold { pending.hold } else { pending.tap };
+        if code == device.caps_lock_code() {
+            self.toggle_caps_lock(device);
+        } else if hold {
+            // so a held shift is seen by HalfKey and FullKey
+            self.key_state.insert(code.into());
+            // and a held revert key switches keymaps like it does pressed on its own
+            self.revert_event(code, KeyState::DOWN, event, device)?;
+        }
+        device.send_mod_code_value(code, false, event)?;
+        device.synchronize()?;
+        self.tap_hold_down.insert(pending.code, code);
+        self.release_deferred_one_shot(event, device, now)?;
+        for (code, value, time) in pending.buffered {
+            event.set_code(code);
+            event.set_value(value);
+            self.tap_hold_event_at(event, device, time)?;
+        }
+        event.set_code(orig_code);
+        event.set_value(orig_value);
+        Ok(())
+    }
+
+    fn tap_hold_event_at(&mut self, event: &mut E, device: &K, now: Instant) -> Result<R> {
+        let code = event.code();
+        let value = event.value();
+        if let Some(pending) = &self.tap_hold_pending {
+            let hold = if now.duration_since(pending.since) >= self.tapping_term {
+                Some(true)
+            } else if code == pending.code {
+                if value != KeyState::UP {
+                    // auto-repeat while we are still deciding
+                    return device.block_key();
+                }
+                Some(false)
+            } else if value == KeyState::DOWN && self.hold_on_other_key_press {
+                Some(true)
+            } else if value == KeyState::UP
+                && self.permissive_hold
+                && pending
+                    .buffered
+                    .iter()
+                    .any(|(c, v, _)| *c == code && *v == KeyState::DOWN)
+            {
+                // another key was pressed *and* released while held
+                Some(true)
+            } else {
+                None
+            };
+            match hold {
+                Some(hold) => {
+                    self.resolve_tap_hold(hold, event, device, now)?;
+                    // replaying may have left a new tap-hold key pending, which this event now needs to go through
+                    return self.tap_hold_event_at(event, device, now);
+                }
+                None => {
+                    if value != KeyState::OTHER {
+                        if let Some(pending) = &mut self.tap_hold_pending {
+                            pending.buffered.push((code, value, now));
+                        }
+                    }
+                    return device.block_key();
+                }
+            }
+        }
+        if let Some(down_code) = self.tap_hold_down.get(&code).copied() {
+            // tap-hold key we already decided on
+            if value == KeyState::UP {
+                self.tap_hold_down.remove(&code);
+                // only a held code was added to key_state
+                if down_code != device.caps_lock_code() && self.key_state.remove(&down_code.into())
+                {
+                    if let Some(ret) = self.revert_event(down_code, value, event, device)? {
+                        return Ok(ret);
+                    }
+                }
+            }
+            return device.send_mod_code(down_code, event);
+        }
+        if value == KeyState::DOWN {
+            if let Some(Key::TapHold(tap, hold)) = self.keymaps[self.keymap_index(code)].key(code) {
+                self.one_shot_layer = None;
+                self.tap_hold_pending = Some(PendingTapHold {
+                    code,
+                    tap,
+                    hold,
+                    since: now,
+                    buffered: Vec::new(),
+                });
+                return device.block_key();
+            }
+        }
+        self.map_event(event, device, now)
+    }
+
+    // the keymap to look code up in, the revert keymap while reverting, otherwise the top layer not transparent for code
+    fn keymap_index(&self, code: T) -> usize {
+        if self.reverting {
+            return self.revert_keymap_index;
+        }
+        self.one_shot_layer
+            .iter()
+            .chain(self.layers.iter().rev())
+            .copied()
+            .find(|idx| !matches!(self.keymaps[*idx].key(code), Some(Key::Transparent)))
+            .unwrap_or(self.chosen_keymap_index)
+    }
+
+    fn layer_action(&mut self, action: LayerAction, layer: usize, value: KeyState) {
+        match (action, value) {
+            (LayerAction::Momentary, KeyState::DOWN) => self.layers.push(layer),
+            (LayerAction::Momentary, KeyState::UP) => {
+                if let Some(pos) = self.layers.iter().rposition(|l| *l == layer) {
+                    self.layers.remove(pos);
+                }
+            }
+            (LayerAction::Toggle, KeyState::DOWN) => {
+                match self.layers.iter().rposition(|l| *l == layer) {
+                    Some(pos) => {
+                        self.layers.remove(pos);
+                    }
+                    None => self.layers.push(layer),
+                }
+            }
+            (LayerAction::OneShot, KeyState::DOWN) => self.one_shot_layer = Some(layer),
+            (LayerAction::To, KeyState::DOWN) => {
+                self.chosen_keymap_index = layer;
+                self.layers.clear();
+            }
+            _ => (), // nothing on release or auto-repeat
+        }
+    }
+
+    fn play_macro(
+        &mut self,
+        macro_index: usize,
+        event: &mut E,
+        device: &K,
+        now: Instant,
+    ) -> Result<()> {
+        let steps = self.macros[macro_index].clone();
+        self.play_steps(&steps, event, device, now)
+    }
+
+    // plays steps up to the first delay, the rest are left in macro_pending for tick to play once it has passed
+    fn play_steps(
+        &mut self,
+        steps: &[MacroStep<T>],
+        event: &mut E,
+        device: &K,
+        now: Instant,
+    ) -> Result<()> {
+        if let Some(pending) = &mut self.macro_pending {
+            // still waiting on a delay, these play after the steps before them
+            pending.steps.extend(steps.iter());
+            return Ok(());
+        }
+        for (i, step) in steps.iter().enumerate() {
+            match *step {
+                MacroStep::Down(code) => {
+                    device.send_mod_code_value(code, false, event)?;
+                    device.synchronize()?;
+                }
+                MacroStep::Up(code) => {
+                    device.send_mod_code_value(code, true, event)?;
+                    device.synchronize()?;
+                }
+                MacroStep::Delay(delay) => {
+                    self.macro_pending = Some(PendingMacro {
+                        steps: steps[i + 1..].iter().copied().collect(),
+                        until: now + delay,
+                        buffered: Vec::new(),
+                    });
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // plays the macro steps left after a delay, then sends on the events that came in while they played
+    fn resume_macro(&mut self, event: &mut E, device: &K, now: Instant) -> Result<()> {
+        let pending = match self.macro_pending.take() {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+        let steps: Vec<MacroStep<T>> = pending.steps.into();
+        self.play_steps(&steps, event, device, now)?;
+        if let Some(waiting) = &mut self.macro_pending {
+            // another delay
+            waiting.buffered = pending.buffered;
+            return Ok(());
+        }
+        let (orig_code, orig_value) = (event.code(), event.value());
+        for (code, value, time) in pending.buffered {
+            event.set_code(code);
+            event.set_value(value);
+            self.send_event_at(event, device, time)?;
+        }
+        event.set_code(orig_code);
+        event.set_value(orig_value);
+        device.synchronize()?;
+        Ok(())
+    }
+
+    // types the shifted character if shift or caps lock is on, with shift let go so it doesn't change the keys typed
+    fn type_unicode(
+        &mut self,
+        noshift: char,
+        shift: char,
+        event: &mut E,
+        device: &K,
+        now: Instant,
+    ) -> Result<()> {
+        let left_shift = device.left_shift_code();
+        let right_shift = device.right_shift_code();
+        let held: Vec<T> = [left_shift, right_shift]
+            .iter()
+            .copied()
+            .filter(|code| self.key_state.contains(&(*code).into()))
+            .collect();
+        let shifted = !held.is_empty();
+        let caps_lock = self.key_state.contains(&device.caps_lock_code().into());
+        let c = if caps_lock != shifted { shift } else { noshift };
+        let steps: Vec<MacroStep<T>> = held
+            .iter()
+            .map(|code| MacroStep::Up(*code))
+            .chain(self.unicode_steps[&c].iter().copied())
+            .chain(held.iter().map(|code| MacroStep::Down(*code)))
+            .collect();
+        self.play_steps(&steps, event, device, now)
+    }
+
+    // a dead key starts a compose sequence, keys typed after it are swallowed until they are one and its result is sent,
+    // Some if event was part of one, the dead key itself types nothing, so it is dropped if no sequence follows
+    fn compose_event(
+        &mut self,
+        idx: usize,
+        event: &mut E,
+        device: &K,
+        now: Instant,
+    ) -> Result<Option<R>> {
+        let code = event.code();
+        let value = event.value();
+        if let Some(pos) = self.compose_swallowed.iter().position(|c| *c == code) {
+            if value == KeyState::UP {
+                self.compose_swallowed.remove(pos);
+            }
+            return device.block_key().map(Some);
+        }
+        // shift can be held for the keys of a sequence
+        if value != KeyState::DOWN
+            || code == device.left_shift_code()
+            || code == device.right_shift_code()
+        {
+            return Ok(None);
+        }
+        let key = self.keymaps[idx].key(code);
+        let (dead_key, mut typed) = match (self.compose_pending.take(), key) {
+            (pending, Some(Key::Dead(dead_key))) => {
+                // a dead key gives up on any sequence before it
+                if let Some((_, typed)) = pending {
+                    self.retype(&typed, event, device, now)?;
+                }
+                self.one_shot_layer = None;
+                self.compose_pending = Some((dead_key, Vec::new()));
+                return device.block_key().map(Some);
+            }
+            (Some(pending), _) => pending,
+            (None, _) => return Ok(None),
+        };
+        // matched by what the key sends, so sequences are written in the layout being typed
+        typed.push(match key {
+            Some(Key::Direct(mapped)) => mapped,
+            _ => code,
+        });
+        let sequences = &self.dead_keys[dead_key];
+        let complete = sequences.iter().position(|(keys, _)| *keys == typed);
+        if complete.is_none() && !sequences.iter().any(|(keys, _)| keys.starts_with(&typed)) {
+            // not a sequence after all, the keys before this one are typed like normal and this one goes on
+            typed.pop();
+            self.retype(&typed, event, device, now)?;
+            return Ok(None);
+        }
+        self.compose_swallowed.push(code);
+        match complete {
+            Some(complete) => match &self.dead_keys[dead_key][complete].1 {
+                ComposeResult::Unicode(noshift, shift) => {
+                    let (noshift, shift) = (*noshift, *shift);
+                    self.type_unicode(noshift, shift, event, device, now)?
+                }
+                ComposeResult::Steps(steps) => {
+                    let steps = steps.clone();
+                    self.play_steps(&steps, event, device, now)?
+                }
+            },
+            None => self.compose_pending = Some((dead_key, typed)),
+        }
+        device.block_key().map(Some)
+    }
+
+    // taps keys swallowed by a compose sequence that didn't finish
+    fn retype(&mut self, typed: &[T], event: &mut E, device: &K, now: Instant) -> Result<()> {
+        let steps: Vec<MacroStep<T>> = typed
+            .iter()
+            .flat_map(|code| [MacroStep::Down(*code), MacroStep::Up(*code)])
+            .collect();
+        self.play_steps(&steps, event, device, now)
+    }
+
+    fn held(&self, code: T) -> bool {
+        self.key_state.contains(&code.into())
+    }
+
+    // the code a HalfKey or FullKey sends with the keys held now, None for other keys
+    fn half_key_code(&self, key: Option<Key<T>>, device: &K) -> Option<T> {
+        match key? {
+            Key::HalfKey(half) => Some(half.code),
+            Key::FullKey(noshift, shift) => {
+                let shifted =
+                    self.held(device.left_shift_code()) || self.held(device.right_shift_code());
+                if self.held(device.caps_lock_code()) != shifted {
+                    Some(shift.code)
+                } else {
+                    Some(noshift.code)
+                }
+            }
+            _ => None,
+        }
+    }
+
+    // whether the modifiers held are the ones rule wants
+    fn mod_rule_matches(&self, rule: &ModRule<T>) -> bool {
+        rule.mods
+            .iter()
+            .all(|codes| codes.iter().any(|code| self.held(*code)))
+            && (!rule.only
+                || self
+                    .modifiers
+                    .iter()
+                    .filter(|code| self.held(**code))
+                    .all(|code| rule.mods.iter().any(|codes| codes.contains(code))))
+    }
+
+    // the key a mod_rules rule sends for event instead of the keymap's, and the modifiers it let go of when pressed,
+    // which map_event presses again once it is released
+    fn mod_rule_event(&mut self, event: &mut E, device: &K) -> Result<Option<(Key<T>, Vec<T>)>> {
+        let code = event.code();
+        let (rule, consumed) = match event.value() {
+            KeyState::DOWN => {
+                let rule = match self
+                    .mod_rules
+                    .iter()
+                    .position(|rule| rule.key == code && self.mod_rule_matches(rule))
+                {
+                    Some(rule) => rule,
+                    None => return Ok(None),
+                };
+                let consumed: Vec<T> = if self.mod_rules[rule].consume {
+                    self.mod_rules[rule]
+                        .mods
+                        .iter()
+                        .flatten()
+                        .copied()
+                        .filter(|code| self.held(*code))
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+                for code in consumed.iter() {
+                    device.send_mod_code_value(*code, true, event)?;
+                }
+                if !consumed.is_empty() {
+                    device.synchronize()?;
+                }
+                self.mod_rules_down.insert(code, (rule, consumed.clone()));
+                (rule, consumed)
+            }
+            KeyState::UP => match self.mod_rules_down.remove(&code) {
+                Some(down) => down,
+                None => return Ok(None),
+            },
+            KeyState::OTHER => match self.mod_rules_down.get(&code) {
+                Some(down) => down.clone(),
+                None => return Ok(None),
+            },
+        };
+        Ok(Some((self.mod_rules[rule].send, consumed)))
+    }
+
+    fn toggle_caps_lock(&mut self, device: &K) {
+        let caps_lock = device.caps_lock_code().into();
+        if !self.key_state.remove(&caps_lock) {
+            self.key_state.insert(caps_lock);
+        }
+    }
+
+    // a revert_default_keys key switches to revert_keymap_index while it is held, Some if its release was sent here
+    #[cfg_attr(target_os = "macos", allow(unused_variables))]
+    fn revert_event(
+        &mut self,
+        code: T,
+        value: KeyState,
+        event: &mut E,
+        device: &K,
+    ) -> Result<Option<R>> {
+        if self.revert_default_keys.contains(&code) {
+            match value {
+                KeyState::DOWN => {
+                    // todo: should we release currently held keys and then press them back down here, kinda the opposite of below? not for now...
+                    self.reverting = true
+                }
+                KeyState::UP => {
+                    self.reverting = false;
+                    #[cfg(not(target_os = "macos"))]
+                    {
+                        // need to release all currently held down keys, except this one, otherwise ctrl+c will get c stuck because code c value 1 will be sent, but then we'll let go of ctrl, and code j value 0 is sent, so c is never released
+                        for idx in self.key_state.drain() {
+                            device.send_mod_code_value(
+                                T::try_from(idx).unwrap_or_else(|_| {
+                                    panic!("cannot convert from usize to T ????")
+                                }),
+                                true,
+                                event,
+                            )?;
+                        }
+                        // todo: seems like we should not send this here, and instead just set the original code back, and pass it through the keymaps?
+                        return device.send_mod_code_value(code, true, event).map(Some);
+                    }
+                }
+                _ => (), // do nothing for 2
+            }
+        }
+        Ok(None)
+    }
+
+    fn map_event(&mut self, event: &mut E, device: &K, now: Instant) -> Result<R> {
+        let value = event.value();
+        if value != KeyState::OTHER {
+            // todo: index check here...
+            if event.code() == device.caps_lock_code() {
+                if value == KeyState::DOWN {
+                    self.toggle_caps_lock(device);
+                }
+            } else if value == KeyState::DOWN {
+                self.key_state.insert(event.code().into());
+            } else {
+                self.key_state.remove(&event.code().into());
+            }
+            let mut switch_layout_keys_pressed = true;
+            for layout_switch_key in self.switch_layout_keys.iter_mut() {
+                if !self.key_state.contains(layout_switch_key) {
+                    switch_layout_keys_pressed = false;
+                    break;
+                }
+            }
+            //println!("switch_layout_keys_pressed: {}", self.switch_layout_keys_pressed);
+            if switch_layout_keys_pressed {
+                if let Some(new_index) = self.keymap_index_keys.get(&event.code()) {
+                    self.chosen_keymap_index = *new_index; // todo: what if revert_default_key is held? for now ignore
+                    return device.block_key(); // we don't want to also send this keypress, so bail
+                }
+            }
+            if let Some(ret) = self.revert_event(event.code(), value, event, device)? {
+                return Ok(ret);
+            }
+        }
+        let code = event.code();
+        let idx = match value {
+            KeyState::DOWN => {
+                let idx = self.keymap_index(code);
+                self.pressed_on.insert(code, idx);
+                idx
+            }
+            KeyState::UP => self
+                .pressed_on
+                .remove(&code)
+                .unwrap_or_else(|| self.keymap_index(code)),
+            KeyState::OTHER => self
+                .pressed_on
+                .get(&code)
+                .copied()
+                .unwrap_or_else(|| self.keymap_index(code)),
+        };
+        if let Some(ret) = self.compose_event(idx, event, device, now)? {
+            return Ok(ret);
+        }
+        let mod_rule = self.mod_rule_event(event, device)?;
+        let key = match mod_rule {
+            Some((send, _)) => Some(send),
+            None => self.keymaps[idx].key(code),
+        };
+        let ret = match key {
+            Some(Key::Layer(action, layer)) => {
+                self.layer_action(action, layer, value);
+                device.block_key()?
+            }
+            Some(Key::Macro(macro_index)) => {
+                if value == KeyState::DOWN {
+                    self.one_shot_layer = None;
+                    self.play_macro(macro_index, event, device, now)?;
+                }
+                device.block_key()?
+            }
+            Some(Key::Unicode(noshift, shift)) => {
+                // typed again on every auto-repeat, like a key would be
+                if value != KeyState::UP {
+                    self.one_shot_layer = None;
+                    self.type_unicode(noshift, shift, event, device, now)?;
+                }
+                device.block_key()?
+            }
+            // pressing it was handled by compose_event
+            Some(Key::Dead(_)) => device.block_key()?,
+            _ => {
+                match value {
+                    KeyState::DOWN => {
+                        self.one_shot_layer = None;
+                        match self.half_key_code(key, device) {
+                            Some(sent) => self.half_key_codes.insert(code, sent),
+                            None => self.half_key_codes.remove(&code),
+                        };
+                    }
+                    KeyState::UP => {
+                        self.half_key_codes.remove(&code);
+                    }
+                    KeyState::OTHER => (),
+                }
+                match (value, &mod_rule) {
+                    // shift was changed for the press and stays that way, and shift changing since doesn't change the code
+                    (KeyState::OTHER, _) if self.half_key_codes.contains_key(&code) => {
+                        device.send_mod_code(self.half_key_codes[&code], event)?
+                    }
+                    (_, Some((send, _))) => send.send_event(&self.key_state, event, device)?,
+                    (_, None) => self.keymaps[idx].send_event(&self.key_state, event, device)?,
+                }
+            }
+        };
+        if let Some((_, consumed)) = mod_rule {
+            // modifiers a rule let go of come back down if they are still held
+            let still_held: Vec<T> = consumed.into_iter().filter(|c| self.held(*c)).collect();
+            if value == KeyState::UP && !still_held.is_empty() {
+                device.synchronize()?;
+                for code in still_held {
+                    device.send_mod_code_value(code, false, event)?;
+                }
+            }
+        }
+        Ok(ret)
     }
 }
 
-#[derive(Clone, Copy)]
-enum Key<T>
-where
-    T: Copy + Clone,
-{
-    Noop,
-    Direct(T),
-    HalfKey(HalfInvertedKey<T>),
-    FullKey(HalfInvertedKey<T>, HalfInvertedKey<T>),
+// unmapped codes are not stored, so many keymaps over the whole code space stay small
+struct KeyMap<T: Into<usize> + Copy> {
+    keymap: HashMap<usize, Key<T>>,
 }
 
-impl<K, T, E, R> KeyMapper<K, T, E, R> for Key<T>
+impl<T: Into<usize> + Copy> KeyMap<T> {
+    pub fn new() -> Self {
+        KeyMap {
+            keymap: HashMap::new(),
+        }
+    }
+
+    pub fn map(&mut self, from: T, to: Key<T>) {
+        self.keymap.insert(from.into(), to);
+    }
+}
+
+impl<K, T, E, R> KeyMapper<K, T, E, R> for KeyMap<T>
 where
     T: Into<usize> + Copy,
     E: KeyEvent<T>,
     K: Keyboard<T, E, R>,
 {
-    fn send_event(&self, key_state: &[bool], event: &mut E, device: &K) -> Result<R> {
-        match *self {
-            Key::Noop => device.send(event),
-            Key::Direct(code) => device.send_mod_code(code, event),
-            Key::HalfKey(ref key_half) => key_half.send_event(key_state, event, device),
-            Key::FullKey(ref noshift_half, ref shift_half) => {
-                let left_shift = key_state[device.left_shift_code().into()];
-                let right_shift = key_state[device.right_shift_code().into()];
-                let caps_lock = key_state[device.caps_lock_code().into()];
-                if caps_lock != (left_shift || right_shift) {
-                    device.send_half_inverted_key(
-                        shift_half,
-                        event,
+    fn send_event(&self, key_state: &HashSet<usize>, event: &mut E, device: &K) -> Result<R> {
+        match self.keymap.get(&event.code().into()) {
+            Some(key) => key.send_event(key_state, event, device),
+            None => Key::Noop.send_event(key_state, event, device),
+        }
+    }
+
+    fn key(&self, code: T) -> Option<Key<T>> {
+        self.keymap.get(&code.into()).copied()
+    }
+}
+
+// codes not in the map are sent unchanged
+struct CodeKeyMap<T: Into<usize> + Copy> {
+    keymap: HashMap<usize, T>,
+}
+
+impl<T: Into<usize> + Copy> CodeKeyMap<T> {
+    pub fn new() -> Self {
+        CodeKeyMap {
+            keymap: HashMap::new(),
+        }
+    }
+
+    pub fn map(&mut self, from: T, to: T) {
+        self.keymap.insert(from.into(), to);
+    }
+
+    fn code(&self, code: T) -> T {
+        self.keymap.get(&code.into()).copied().unwrap_or(code)
+    }
+}
+
+impl<K, T, E, R> KeyMapper<K, T, E, R> for CodeKeyMap<T>
+where
+    T: Into<usize> + Copy,
+    E: KeyEvent<T>,
+    K: Keyboard<T, E, R>,
+{
+    fn send_event(&self, _key_state: &HashSet<usize>, event: &mut E, device: &K) -> Result<R> {
+        device.send_mod_code(self.code(event.code()), event)
+    }
+
+    fn key(&self, code: T) -> Option<Key<T>> {
+        Some(Key::Direct(self.code(code)))
+    }
+}
+
+impl<T> HalfInvertedKey<T>
+where
+    T: Into<usize> + Copy + Eq + Hash,
+{
+    fn describe(&self, names: &HashMap<T, &str>, invert_shift: bool) -> (String, String) {
+        let mut key = String::new();
+        let mut flags = Vec::new();
+        if self.capslock_nomodify {
+            key.push(CAPS_MODIFY_KEY_FLAG);
+            flags.push("caps lock modifies");
+        }
+        if invert_shift {
+            key.push(INVERT_KEY_FLAG);
+            flags.push("shift inverted");
+        }
+        key.push_str(&key_name(names, self.code));
+        (key, flags.join(", "))
+    }
+}
+
+// todo:capslock_nomodify is like a whole-key thing, not a half-key thing, split code/invert_shift to own struct, send into send_key from *InvertedKey, maybe anyway, consider it, maybe 1 char for whole key and another for half?
+#[derive(Clone, Copy)]
+pub struct HalfInvertedKey<T: Clone + Copy> {
+    pub code: T,
+    // code this is describing
+    pub invert_shift: bool,
+    // true to invert shift for this code
+    pub capslock_nomodify: bool,
+    // true means capslock does not normally modify this, but you would like it to
+}
+
+impl<K, T, E, R> KeyMapper<K, T, E, R> for HalfInvertedKey<T>
+where
+    T: Into<usize> + Clone + Copy,
+    E: KeyEvent<T>,
+    K: Keyboard<T, E, R>,
+{
+    fn send_event(&self, key_state: &HashSet<usize>, event: &mut E, device: &K) -> Result<R> {
+        let left_shift = key_state.contains(&device.left_shift_code().into());
+        let right_shift = key_state.contains(&device.right_shift_code().into());
+        let caps_lock = key_state.contains(&device.caps_lock_code().into());
+        device.send_half_inverted_key(self, event, left_shift, right_shift, caps_lock)
+    }
+}
+
+#[derive(Clone, Copy)]
+#[allow(clippy::enum_variant_names)]
+pub enum Key<T>
+where
+    T: Copy + Clone,
+{
+    Noop,
+    Direct(T),
+    HalfKey(HalfInvertedKey<T>),
+    FullKey(HalfInvertedKey<T>, HalfInvertedKey<T>),
+    // sends the first code when tapped, second when held, decided by KeyMaps
+    TapHold(T, T),
+    // changes active layers, handled by KeyMaps
+    Layer(LayerAction, usize),
+    // use the key from the layer below this one
+    Transparent,
+    // plays back the macro at this index when pressed, handled by KeyMaps
+    Macro(usize),
+    // moves the pointer or scrolls by this amount, on press and on every auto-repeat
+    Mouse(MouseMove, i32),
+    // types the first character, or the second when shifted, handled by KeyMaps
+    Unicode(char, char),
+    // starts a compose sequence with the dead key at this index, handled by KeyMaps
+    Dead(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LayerAction {
+    // layer active while held
+    Momentary,
+    // layer active until pressed again
+    Toggle,
+    // layer active for the next key pressed only
+    OneShot,
+    // layer becomes the chosen keymap, like switch_layout_keys does
+    To,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MouseMove {
+    Up,
+    Down,
+    Left,
+    Right,
+    WheelUp,
+    WheelDown,
+    WheelLeft,
+    WheelRight,
+}
+
+const MOUSE_MOVES: [(&str, MouseMove); 8] = [
+    ("MOUSE_UP", MouseMove::Up),
+    ("MOUSE_DOWN", MouseMove::Down),
+    ("MOUSE_LEFT", MouseMove::Left),
+    ("MOUSE_RIGHT", MouseMove::Right),
+    ("WHEEL_UP", MouseMove::WheelUp),
+    ("WHEEL_DOWN", MouseMove::WheelDown),
+    ("WHEEL_LEFT", MouseMove::WheelLeft),
+    ("WHEEL_RIGHT", MouseMove::WheelRight),
+];
+
+impl MouseMove {
+    // used when a keymap doesn't give an amount, pixels for the pointer and clicks for the wheel
+    fn default_amount(self) -> i32 {
+        match self {
+            MouseMove::Up | MouseMove::Down | MouseMove::Left | MouseMove::Right => 10,
+            _ => 1,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        MOUSE_MOVES.iter().find(|m| m.1 == self).unwrap().0
+    }
+}
+
+impl<T> Key<T>
+where
+    T: Into<usize> + Copy + Eq + Hash,
+{
+    // (how this is written in a keymap, its flags spelled out)
+    fn describe(
+        &self,
+        names: &HashMap<T, &str>,
+        macro_names: &[String],
+        dead_key_names: &[String],
+    ) -> (String, String) {
+        match *self {
+            Key::Noop => (String::new(), String::new()),
+            Key::Direct(code) => (key_name(names, code), String::new()),
+            Key::HalfKey(key) => key.describe(names, key.invert_shift),
+            Key::FullKey(noshift_half, shift_half) => {
+                let (noshift, noshift_flags) =
+                    noshift_half.describe(names, noshift_half.invert_shift);
+                // the shift half is stored inverted from how it is written
+                let (shift, shift_flags) = shift_half.describe(names, !shift_half.invert_shift);
+                let flags = match (noshift_flags.is_empty(), shift_flags.is_empty()) {
+                    (_, true) => noshift_flags,
+                    (true, false) => format!("shifted: {}", shift_flags),
+                    (false, false) => format!("{}; shifted: {}", noshift_flags, shift_flags),
+                };
+                (format!("{}{}{}", noshift, HALF_KEY_SEPARATOR, shift), flags)
+            }
+            Key::TapHold(tap, hold) => (
+                format!(
+                    "{}({}, {})",
+                    TAP_HOLD_FUNCTION,
+                    key_name(names, tap),
+                    key_name(names, hold)
+                ),
+                String::new(),
+            ),
+            Key::Layer(action, layer) => {
+                let function = match action {
+                    LayerAction::Momentary => MOMENTARY_LAYER_FUNCTION,
+                    LayerAction::Toggle => TOGGLE_LAYER_FUNCTION,
+                    LayerAction::OneShot => ONE_SHOT_LAYER_FUNCTION,
+                    LayerAction::To => TO_LAYER_FUNCTION,
+                };
+                (format!("{}({})", function, layer), String::new())
+            }
+            Key::Transparent => (TRANSPARENT_KEY.to_string(), String::new()),
+            Key::Macro(index) => (
+                format!("{}({})", MACRO_FUNCTION, macro_names[index]),
+                String::new(),
+            ),
+            Key::Mouse(mouse_move, amount) if amount == mouse_move.default_amount() => {
+                (mouse_move.name().to_string(), String::new())
+            }
+            Key::Mouse(mouse_move, amount) => (
+                format!("{}{}{}", mouse_move.name(), HALF_KEY_SEPARATOR, amount),
+                String::new(),
+            ),
+            Key::Dead(index) => (dead_key_names[index].clone(), String::new()),
+            Key::Unicode(noshift, shift) if noshift == shift => {
+                (unicode_name(noshift), String::new())
+            }
+            Key::Unicode(noshift, shift) => (
+                format!(
+                    "{}{}{}",
+                    unicode_name(noshift),
+                    HALF_KEY_SEPARATOR,
+                    unicode_name(shift)
+                ),
+                String::new(),
+            ),
+        }
+    }
+}
+
+fn unicode_name(c: char) -> String {
+    format!("{}{:04X}", UNICODE_PREFIX, c as u32)
+}
+
+// the name code is written as, the shortest if it has more than one
+fn key_names<T: Copy + Eq + Hash>(key_map: &HashMap<&'static str, T>) -> HashMap<T, &'static str> {
+    let mut names: HashMap<T, &'static str> = HashMap::with_capacity(key_map.len());
+    for (name, code) in key_map.iter() {
+        let shortest = names.entry(*code).or_insert(name);
+        if (name.len(), *name) < (shortest.len(), *shortest) {
+            *shortest = name;
+        }
+    }
+    names
+}
+
+fn key_name<T: Into<usize> + Copy + Eq + Hash>(names: &HashMap<T, &str>, code: T) -> String {
+    match names.get(&code) {
+        Some(name) => name.to_string(),
+        None => format!("{}{}", KEY_CODE_PREFIX, code.into()),
+    }
+}
+
+impl<K, T, E, R> KeyMapper<K, T, E, R> for Key<T>
+where
+    T: Into<usize> + Copy,
+    E: KeyEvent<T>,
+    K: Keyboard<T, E, R>,
+{
+    fn send_event(&self, key_state: &HashSet<usize>, event: &mut E, device: &K) -> Result<R> {
+        match *self {
+            // TapHold, Layer, Macro, Unicode and Dead are handled by KeyMaps before getting here, Transparent with nothing below is a Noop
+            Key::Noop
+            | Key::TapHold(_, _)
+            | Key::Layer(_, _)
+            | Key::Transparent
+            | Key::Macro(_)
+            | Key::Unicode(_, _)
+            | Key::Dead(_) => device.send(event),
+            Key::Direct(code) => device.send_mod_code(code, event),
+            Key::Mouse(_, _) if event.value() == KeyState::UP => device.block_key(),
+            Key::Mouse(mouse_move, amount) => device.send_mouse_move(mouse_move, amount, event),
+            Key::HalfKey(ref key_half) => key_half.send_event(key_state, event, device),
+            Key::FullKey(ref noshift_half, ref shift_half) => {
+                let left_shift = key_state.contains(&device.left_shift_code().into());
+                let right_shift = key_state.contains(&device.right_shift_code().into());
+                let caps_lock = key_state.contains(&device.caps_lock_code().into());
+                if caps_lock != (left_shift || right_shift) {
+                    device.send_half_inverted_key(
+                        shift_half,
+                        event,
                         left_shift,
                         right_shift,
                         caps_lock,
@@ -490,171 +2424,1792 @@ where
             }
         }
     }
-}
+}
+
+pub trait DeviceIds {
+    fn bustype(&self) -> Option<u16>;
+    fn vendor(&self) -> Option<u16>;
+    fn product(&self) -> Option<u16>;
+    fn version(&self) -> Option<u16>;
+
+    fn name(&self) -> Option<&str> {
+        None
+    }
+
+    // physical path, like usb-0000:00:14.0-1/input0
+    fn phys(&self) -> Option<&str> {
+        None
+    }
+
+    // unique id, like a bluetooth MAC address, most USB devices don't have one
+    fn uniq(&self) -> Option<&str> {
+        None
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "toml_serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "toml_serde", serde(deny_unknown_fields))]
+pub struct DeviceMatcher {
+    pub bustype: Option<u16>,
+    pub vendor: Option<u16>,
+    pub product: Option<u16>,
+    pub version: Option<u16>,
+    // these are globs, * matches anything, ? matches any 1 character
+    pub name: Option<String>,
+    pub phys: Option<String>,
+    pub uniq: Option<String>,
+    // only for grab, name of the profile in profiles to use for matching devices
+    pub profile: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "toml_serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "toml_serde", serde(deny_unknown_fields))]
+pub struct DeviceMatchers {
+    #[cfg_attr(feature = "toml_serde", serde(default))]
+    grab: Vec<DeviceMatcher>,
+    #[cfg_attr(feature = "toml_serde", serde(default = "default_skip"))]
+    skip: Vec<DeviceMatcher>,
+    // a device has to support all of these keys to be a keyboard
+    #[cfg_attr(feature = "toml_serde", serde(default = "default_required_keys"))]
+    required_keys: Vec<String>,
+    // a device supporting any of these keys is not a keyboard
+    #[cfg_attr(feature = "toml_serde", serde(default = "default_forbidden_keys"))]
+    forbidden_keys: Vec<String>,
+    // a device has to support all of these event types to be a keyboard
+    #[cfg_attr(feature = "toml_serde", serde(default = "default_required_events"))]
+    required_events: Vec<String>,
+    // devices matching any of these are grabbed whether they look like a keyboard or not, and even if grab or skip say otherwise
+    #[cfg_attr(feature = "toml_serde", serde(default))]
+    always_grab: Vec<DeviceMatcher>,
+}
+
+impl Default for DeviceMatchers {
+    fn default() -> Self {
+        Self {
+            grab: Default::default(),
+            skip: default_skip(),
+            required_keys: default_required_keys(),
+            forbidden_keys: default_forbidden_keys(),
+            required_events: default_required_events(),
+            always_grab: Default::default(),
+        }
+    }
+}
+
+pub fn default_skip() -> Vec<DeviceMatcher> {
+    vec![
+        // 0x1050 is Yubico
+        DeviceMatcher {
+            vendor: Some(0x1050),
+            ..Default::default()
+        },
+    ]
+}
+
+// keyboards have WASD and no left mouse button, yes this is fairly random but probably good enough
+pub fn default_required_keys() -> Vec<String> {
+    vec![
+        "W".to_string(),
+        "A".to_string(),
+        "S".to_string(),
+        "D".to_string(),
+    ]
+}
+
+pub fn default_forbidden_keys() -> Vec<String> {
+    vec!["BTN_LEFT".to_string()]
+}
+
+pub fn default_required_events() -> Vec<String> {
+    vec!["EV_KEY".to_string()]
+}
+
+#[inline]
+fn um(rule: Option<u16>, data: Option<u16>) -> bool {
+    rule.is_none() || rule == data
+}
+
+#[inline]
+fn um_glob(rule: &Option<String>, data: Option<&str>) -> bool {
+    match (rule, data) {
+        (None, _) => true,
+        (Some(rule), Some(data)) => glob_matches(rule, data),
+        (Some(_), None) => false,
+    }
+}
+
+// * matches any number of characters, ? matches exactly 1, anything else only itself
+fn glob_matches(glob: &str, text: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut g, mut t) = (0, 0);
+    // where the last * was in glob, and where in text it started matching
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if g < glob.len() && (glob[g] == '?' || glob[g] == text[t]) {
+            g += 1;
+            t += 1;
+        } else if g < glob.len() && glob[g] == '*' {
+            star = Some((g, t));
+            g += 1;
+        } else if let Some((star_g, star_t)) = star {
+            // let the last * match 1 more character and try again
+            g = star_g + 1;
+            t = star_t + 1;
+            star = Some((star_g, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    glob[g..].iter().all(|c| *c == '*')
+}
+
+impl DeviceMatcher {
+    pub fn matches(&self, device: &dyn DeviceIds) -> bool {
+        um(self.bustype, device.bustype())
+            && um(self.vendor, device.vendor())
+            && um(self.product, device.product())
+            && um(self.version, device.version())
+            && um_glob(&self.name, device.name())
+            && um_glob(&self.phys, device.phys())
+            && um_glob(&self.uniq, device.uniq())
+    }
+}
+
+impl DeviceMatchers {
+    pub fn grab(&self, device: &dyn DeviceIds) -> bool {
+        // we should grab this device if grab is empty or any single entry matches
+        (self.grab.is_empty() || self.grab.iter().any(|d| d.matches(device)))
+            // and skip is empty or no skip entry matches
+            && (self.skip.is_empty() || !self.skip.iter().any(|d| d.matches(device)))
+    }
+
+    /// True if device should be grabbed without checking if it looks like a keyboard
+    pub fn always_grab(&self, device: &dyn DeviceIds) -> bool {
+        self.always_grab.iter().any(|d| d.matches(device))
+    }
+
+    pub fn required_keys(&self) -> &[String] {
+        &self.required_keys
+    }
+
+    pub fn forbidden_keys(&self) -> &[String] {
+        &self.forbidden_keys
+    }
+
+    pub fn required_events(&self) -> &[String] {
+        &self.required_events
+    }
+
+    /// The profile named by the first always_grab or grab entry matching device, if any
+    pub fn profile(&self, device: &dyn DeviceIds) -> Option<&str> {
+        self.always_grab
+            .iter()
+            .chain(self.grab.iter())
+            .find(|d| d.matches(device))
+            .and_then(|d| d.profile.as_deref())
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "toml_serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "toml_serde", serde(deny_unknown_fields, default))]
+pub struct TapHoldConfig {
+    // held longer than this is a hold, shorter is a tap
+    pub tapping_term_ms: u64,
+    // another key pressed and released while held means hold, even within tapping_term_ms
+    pub permissive_hold: bool,
+    // another key pressed while held means hold, even within tapping_term_ms
+    pub hold_on_other_key_press: bool,
+}
+
+impl Default for TapHoldConfig {
+    fn default() -> Self {
+        Self {
+            tapping_term_ms: 200,
+            permissive_hold: false,
+            hold_on_other_key_press: false,
+        }
+    }
+}
+
+#[cfg(feature = "toml_serde")]
+fn default_combo_term_ms() -> u64 {
+    30
+}
+
+// the kernel's own defaults
+#[cfg(feature = "toml_serde")]
+fn default_repeat_delay_ms() -> u64 {
+    250
+}
+
+#[cfg(feature = "toml_serde")]
+fn default_repeat_period_ms() -> u64 {
+    33
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "toml_serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "toml_serde", serde(rename_all = "snake_case"))]
+pub enum UnicodeMethod {
+    // ctrl+shift+u then the hex code, what GTK and IBus take
+    #[default]
+    CtrlShiftU,
+    // compose_key then a sequence from the compose table, for X's compose key
+    Compose,
+}
+
+#[cfg(feature = "toml_serde")]
+#[derive(serde::Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields, default)]
+pub struct UnicodeConfig {
+    method: UnicodeMethod,
+    compose_key: Option<String>,
+    // character -> keys typed after compose_key, written like a macro
+    compose: HashMap<String, String>,
+}
+
+#[cfg(feature = "toml_serde")]
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RepeatConfig {
+    // held this long before the first repeat
+    #[serde(default = "default_repeat_delay_ms")]
+    delay_ms: u64,
+    // then repeats this often
+    #[serde(default = "default_repeat_period_ms")]
+    period_ms: u64,
+    #[serde(default)]
+    no_repeat: Vec<String>,
+}
+
+#[cfg(feature = "toml_serde")]
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ModRuleConfig {
+    key: String,
+    // all held for the rule to apply, CTRL, SHIFT, ALT or GUI means either side
+    #[serde(default)]
+    mods: Vec<String>,
+    send: String,
+    // no other modifiers may be held
+    #[serde(default)]
+    only: bool,
+    // mods are let go while send is
+    #[serde(default)]
+    consume: bool,
+}
+
+#[cfg(feature = "toml_serde")]
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ComboConfig {
+    keys: Vec<String>,
+    send: String,
+    #[serde(default = "default_combo_term_ms")]
+    term_ms: u64,
+}
+
+// anything set here replaces the top level setting for devices using this profile
+#[cfg(feature = "toml_serde")]
+#[derive(serde::Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfig {
+    // send everything from the device on unchanged, the other settings don't matter then
+    #[serde(default)]
+    pass_through: bool,
+    switch_layout_keys: Option<Vec<String>>,
+    revert_default_keys: Option<Vec<String>>,
+    revert_keymap_index: Option<usize>,
+    default_keymap_index: Option<usize>,
+    keymaps: Option<Vec<String>>,
+    tap_hold: Option<TapHoldConfig>,
+    macros: Option<HashMap<String, String>>,
+    combos: Option<Vec<ComboConfig>>,
+    scroll_button: Option<String>,
+    repeat: Option<RepeatConfig>,
+    one_shot: Option<HashMap<String, u64>>,
+    mod_rules: Option<Vec<ModRuleConfig>>,
+    unicode: Option<UnicodeConfig>,
+    compose: Option<HashMap<String, HashMap<String, String>>>,
+}
+
+#[cfg(feature = "toml_serde")]
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct KeymapConfig {
+    switch_layout_keys: Vec<String>,
+    revert_default_key: Option<String>,
+    revert_default_keys: Option<Vec<String>>,
+    revert_keymap_index: usize,
+    default_keymap_index: usize,
+    keymaps: Vec<String>,
+    #[serde(default)]
+    tap_hold: TapHoldConfig,
+    #[serde(default)]
+    macros: HashMap<String, String>,
+    #[serde(default)]
+    combos: Vec<ComboConfig>,
+    // while this button is held pointer movement scrolls instead, it only clicks if released without scrolling
+    scroll_button: Option<String>,
+    // we auto-repeat keys ourselves instead of passing on the keyboard's
+    repeat: Option<RepeatConfig>,
+    // modifier -> milliseconds it waits for the next key after being tapped, 0 waits forever
+    #[serde(default)]
+    one_shot: HashMap<String, u64>,
+    // keys that send something else while certain modifiers are held, first match wins
+    #[serde(default)]
+    mod_rules: Vec<ModRuleConfig>,
+    // how unicode characters in keymaps are typed
+    #[serde(default)]
+    unicode: UnicodeConfig,
+    // dead key -> keys typed after it -> what they compose
+    #[serde(default)]
+    compose: HashMap<String, HashMap<String, String>>,
+    #[serde(default)]
+    devices: DeviceMatchers,
+    #[serde(default)]
+    profiles: HashMap<String, ProfileConfig>,
+}
+
+#[cfg(feature = "toml_serde")]
+fn parse_cfg<P: AsRef<Path>>(path: P) -> Result<KeymapConfig> {
+    use std::io::Read;
+    let mut f = std::fs::File::open(path)?;
+    let mut input = String::new();
+    f.read_to_string(&mut input)?;
+    toml::from_str(&input).map_err(crate::Error::Toml)
+}
+
+#[cfg(not(feature = "toml_serde"))]
+#[derive(Debug, Clone)]
+pub struct ModRuleConfig {
+    key: &'static str,
+    mods: Vec<&'static str>,
+    send: &'static str,
+    only: bool,
+    consume: bool,
+}
+
+#[cfg(not(feature = "toml_serde"))]
+#[derive(Debug, Clone)]
+pub struct ComboConfig {
+    keys: Vec<&'static str>,
+    send: &'static str,
+    term_ms: u64,
+}
+
+#[cfg(not(feature = "toml_serde"))]
+#[derive(Debug, Clone, Default)]
+pub struct UnicodeConfig {
+    method: UnicodeMethod,
+    compose_key: Option<&'static str>,
+    compose: HashMap<&'static str, &'static str>,
+}
+
+#[cfg(not(feature = "toml_serde"))]
+#[derive(Debug, Clone)]
+pub struct RepeatConfig {
+    delay_ms: u64,
+    period_ms: u64,
+    no_repeat: Vec<&'static str>,
+}
+
+#[cfg(not(feature = "toml_serde"))]
+#[derive(Debug, Clone, Default)]
+pub struct ProfileConfig {
+    pass_through: bool,
+    switch_layout_keys: Option<Vec<&'static str>>,
+    revert_default_keys: Option<Vec<&'static str>>,
+    revert_keymap_index: Option<usize>,
+    default_keymap_index: Option<usize>,
+    keymaps: Option<Vec<&'static str>>,
+    tap_hold: Option<TapHoldConfig>,
+    macros: Option<HashMap<&'static str, &'static str>>,
+    combos: Option<Vec<ComboConfig>>,
+    scroll_button: Option<&'static str>,
+    repeat: Option<RepeatConfig>,
+    one_shot: Option<HashMap<&'static str, u64>>,
+    mod_rules: Option<Vec<ModRuleConfig>>,
+    unicode: Option<UnicodeConfig>,
+    compose: Option<HashMap<&'static str, HashMap<&'static str, &'static str>>>,
+}
+
+#[cfg(not(feature = "toml_serde"))]
+#[derive(Debug, Clone)]
+pub struct KeymapConfig {
+    switch_layout_keys: Vec<&'static str>,
+    revert_default_key: Option<&'static str>,
+    revert_default_keys: Option<Vec<&'static str>>,
+    revert_keymap_index: usize,
+    default_keymap_index: usize,
+    keymaps: Vec<&'static str>,
+    tap_hold: TapHoldConfig,
+    macros: HashMap<&'static str, &'static str>,
+    combos: Vec<ComboConfig>,
+    scroll_button: Option<&'static str>,
+    repeat: Option<RepeatConfig>,
+    one_shot: HashMap<&'static str, u64>,
+    mod_rules: Vec<ModRuleConfig>,
+    unicode: UnicodeConfig,
+    compose: HashMap<&'static str, HashMap<&'static str, &'static str>>,
+    devices: DeviceMatchers,
+    profiles: HashMap<&'static str, ProfileConfig>,
+}
+
+#[cfg(not(feature = "toml_serde"))]
+impl Default for KeymapConfig {
+    fn default() -> Self {
+        KeymapConfig {
+            switch_layout_keys: vec!["LEFTSHIFT", "RIGHTSHIFT"],
+
+            // pressing any of these keys reverts to the index specified in revert_keymap_index for only the duration of the pressing
+            // used so QWERTY shortcuts like Ctrl+C still work
+            revert_default_keys: Some(vec!["LCTL", "LGUI", "LALT"]),
+            revert_keymap_index: 0,
+
+            // this is the default index to use when the program first starts
+            // in this case, 2 means Unix Programmer Dvorak
+            default_keymap_index: 2,
+
+            // these are the keymaps available, you can add as many as you want or re-order them, just be aware the mapping is
+            // always done from the first one to all subsequent ones, so you probably want to leave QWERTY or similar up top
+            keymaps: vec![
+                // default key layout, QWERTY in this case
+                r###"
+                ESC, F1,  F2,  F3,  F4,  F5,  F6,  F7,  F8,  F9,  F10, F11, F12,           PSCR,SLCK,BRK,
+                GRV, 1,   2,   3,   4,   5,   6,   7,   8,   9,   0,   MINS,EQL, BSPC,     INS, HOME,PGUP,    NLCK,PSLS,PAST,PMNS,
+                TAB, Q,   W,   E,   R,   T,   Y,   U,   I,   O,   P,   LBRC,RBRC,BSLS,     DEL, END, PGDN,    P7,  P8,  P9,
+                CAPS,A,   S,   D,   F,   G,   H,   J,   K,   L,   SCLN,QUOT,     ENT,                         P4,  P5,  P6,  PPLS,
+                LSFT,Z,   X,   C,   V,   B,   N,   M,   COMM,DOT, SLSH,          RSFT,          UP,           P1,  P2,  P3,
+                LCTL,LGUI,LALT,          SPC,                     RALT,RGUI,APP, RCTL,     LEFT,DOWN,RGHT,    P0,       PDOT,PENT
+                "###,
+                // Dvorak http://en.wikipedia.org/wiki/Dvorak_Simplified_Keyboard
+                // https://www.moparisthebest.com/kbs/standard-dvorak-QwertySecondary.svg
+                r###"
+                ESC, F1,  F2,  F3,  F4,  F5,  F6,  F7,  F8,  F9,  F10, F11, F12,           PSCR,SLCK,BRK,
+                GRV, 1,   2,   3,   4,   5,   6,   7,   8,   9,   0,   LBRC,RBRC,BSPC,     INS, HOME,PGUP,    NLCK,PSLS,PAST,PMNS,
+                TAB, QUOT,COMM,DOT, P,   Y,   F,   G,   C,   R,   L,   SLSH,EQL, BSLS,     DEL, END, PGDN,    P7,  P8,  P9,
+                CAPS,A,   O,   E,   U,   I,   D,   H,   T,   N,   S,   MINS,     ENT,                         P4,  P5,  P6,  PPLS,
+                LSFT,SCLN,Q,   J,   K,   X,   B,   M,   W,   V,   Z,             RSFT,          UP,           P1,  P2,  P3,
+                LCTL,LGUI,LALT,          SPC,                     RALT,RGUI,APP, RCTL,     LEFT,DOWN,RGHT,    P0,       PDOT,PENT
+                "###,
+                // Unix Programmer Dvorak - for unix developers who are switching from dvorak
+                // https://www.moparisthebest.com/kbs/programmer-dvorak-NoSecondary-NumpadStandard-NoSwap-StandardNums-SwapAt-SwapPipe.svg
+                r###"
+                ESC,      F1,    F2,     F3,      F4,      F5,   F6,    F7,   F8,   F9,     F10,    F11,   F12,                 PSCR,SLCK,BRK,
+                *^4:*^GRV,  *^7:*1,  *LBRC:*2, *^LBRC:*3, *^RBRC:*4, *^9:*5, *^2:*6,  *^8:*7, *^0:*8, *^EQL:*9, *RBRC:*0, *^1:*^5, *^3:*GRV, BSPC,        INS, HOME,PGUP,    NLCK,   *PSLS:*^9, *PAST:*^0,   *PMNS:*^4,
+                TAB,      *QUOT,  *COMM,   *DOT,     P,       Y,    F,     G,    C,    R,      L,      *SLSH,  *EQL:*^6, *^BSLS,  DEL, END, PGDN,    *P7:^A,  *P8:^B,   *P9:^C,
+                CAPS,     A,     O,      E,       U,       I,    D,     H,    T,    N,      S,      *MINS,          ENT,                            *P4:^D,  *P5:^E,   *P6:^F,     *PPLS:*COMM,
+                LSFT,     *SCLN,  Q,      J,       K,       X,    B,     M,    W,    V,      Z,                     RSFT,             UP,           *P1:*EQL, *P2:X,    *P3:*^SCLN,
+                LCTL,     LGUI,  LALT,                     SPC,                             RALT,   RGUI,  APP,    RCTL,        LEFT,DOWN,RGHT,    *P0:*BSLS,         *PDOT:*SCLN, PENT
+                "###,
+            ],
+            revert_default_key: None, // use revert_default_keys instead
+            tap_hold: Default::default(),
+            macros: Default::default(),
+            combos: Default::default(),
+            scroll_button: None,
+            repeat: None,
+            one_shot: Default::default(),
+            mod_rules: Default::default(),
+            unicode: Default::default(),
+            compose: Default::default(),
+            devices: Default::default(),
+            profiles: Default::default(),
+        }
+    }
+}
+
+impl KeymapConfig {
+    #[cfg(feature = "toml_serde")]
+    pub fn from_cfg<P: AsRef<Path>>(path: P) -> Result<KeymapConfig> {
+        parse_cfg(path)
+    }
+
+    pub fn devices(&self) -> &DeviceMatchers {
+        &self.devices
+    }
+
+    /// The config to build device's KeyMaps from, from the profile its devices.grab entry names, None means pass it through unchanged
+    pub fn for_device(&self, device: &dyn DeviceIds) -> Option<KeymapConfig> {
+        self.for_profile(self.devices.profile(device))
+    }
+
+    /// The config for profile, the top level config if there is no such profile, None means pass-through
+    pub fn for_profile(&self, profile: Option<&str>) -> Option<KeymapConfig> {
+        match profile.and_then(|name| self.profiles.get(name)) {
+            Some(profile) if profile.pass_through => None,
+            Some(profile) => Some(self.with_profile(profile)),
+            None => Some(self.with_profile(&ProfileConfig::default())),
+        }
+    }
+
+    /// Names of every profile, sorted
+    pub fn profile_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.profiles.keys().map(|name| name.as_ref()).collect();
+        names.sort_unstable();
+        names
+    }
+
+    // this config with everything profile sets replaced, and no profiles of its own
+    fn with_profile(&self, profile: &ProfileConfig) -> KeymapConfig {
+        KeymapConfig {
+            switch_layout_keys: profile
+                .switch_layout_keys
+                .clone()
+                .unwrap_or_else(|| self.switch_layout_keys.clone()),
+            revert_default_key: match profile.revert_default_keys {
+                Some(_) => None,
+                None => self.revert_default_key.as_ref().cloned(),
+            },
+            revert_default_keys: profile
+                .revert_default_keys
+                .clone()
+                .or_else(|| self.revert_default_keys.clone()),
+            revert_keymap_index: profile
+                .revert_keymap_index
+                .unwrap_or(self.revert_keymap_index),
+            default_keymap_index: profile
+                .default_keymap_index
+                .unwrap_or(self.default_keymap_index),
+            keymaps: profile
+                .keymaps
+                .clone()
+                .unwrap_or_else(|| self.keymaps.clone()),
+            tap_hold: profile
+                .tap_hold
+                .clone()
+                .unwrap_or_else(|| self.tap_hold.clone()),
+            macros: profile
+                .macros
+                .clone()
+                .unwrap_or_else(|| self.macros.clone()),
+            combos: profile
+                .combos
+                .clone()
+                .unwrap_or_else(|| self.combos.clone()),
+            scroll_button: profile
+                .scroll_button
+                .as_ref()
+                .or(self.scroll_button.as_ref())
+                .cloned(),
+            repeat: profile.repeat.clone().or_else(|| self.repeat.clone()),
+            one_shot: profile
+                .one_shot
+                .clone()
+                .unwrap_or_else(|| self.one_shot.clone()),
+            mod_rules: profile
+                .mod_rules
+                .clone()
+                .unwrap_or_else(|| self.mod_rules.clone()),
+            unicode: profile
+                .unicode
+                .clone()
+                .unwrap_or_else(|| self.unicode.clone()),
+            compose: profile
+                .compose
+                .clone()
+                .unwrap_or_else(|| self.compose.clone()),
+            // only the top level config picks devices and profiles
+            devices: DeviceMatchers::default(),
+            profiles: HashMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use libc::input_id;
+    use std::cell::RefCell;
+
+    use super::*;
+
+    struct TestEvent {
+        code: u16,
+        value: KeyState,
+    }
+
+    impl KeyEvent<u16> for TestEvent {
+        fn code(&self) -> u16 {
+            self.code
+        }
+
+        fn value(&self) -> KeyState {
+            self.value
+        }
+
+        fn set_code(&mut self, code: u16) {
+            self.code = code;
+        }
+
+        fn set_value(&mut self, value: KeyState) {
+            self.value = value;
+        }
+    }
+
+    // records every key sent and mouse move, ignores synchronize
+    #[derive(Default)]
+    struct TestKeyboard {
+        sent: RefCell<Vec<(u16, KeyState)>>,
+        moved: RefCell<Vec<(MouseMove, i32)>>,
+        // like a platform that never calls tick
+        no_tick: bool,
+    }
+
+    impl TestKeyboard {
+        fn take(&self) -> Vec<(u16, KeyState)> {
+            self.sent.replace(Vec::new())
+        }
+    }
+
+    impl Keyboard<u16, TestEvent> for TestKeyboard {
+        fn send(&self, event: &mut TestEvent) -> Result<()> {
+            self.sent.borrow_mut().push((event.code, event.value));
+            Ok(())
+        }
+
+        fn send_mod_code(&self, code: u16, event: &mut TestEvent) -> Result<()> {
+            event.code = code;
+            self.send(event)
+        }
+
+        fn send_mod_code_value(
+            &self,
+            code: u16,
+            up_not_down: bool,
+            _event: &mut TestEvent,
+        ) -> Result<()> {
+            let value = if up_not_down {
+                KeyState::UP
+            } else {
+                KeyState::DOWN
+            };
+            self.sent.borrow_mut().push((code, value));
+            Ok(())
+        }
+
+        fn synchronize(&self) -> Result<()> {
+            Ok(())
+        }
+
+        fn left_shift_code(&self) -> u16 {
+            LSFT
+        }
+
+        fn right_shift_code(&self) -> u16 {
+            RSFT
+        }
+
+        fn caps_lock_code(&self) -> u16 {
+            CAPS
+        }
+
+        fn block_key(&self) -> Result<()> {
+            Ok(())
+        }
+
+        fn drives_tick(&self) -> bool {
+            !self.no_tick
+        }
+
+        fn send_mouse_move(
+            &self,
+            mouse_move: MouseMove,
+            amount: i32,
+            _event: &mut TestEvent,
+        ) -> Result<()> {
+            self.moved.borrow_mut().push((mouse_move, amount));
+            Ok(())
+        }
+    }
+
+    const ESC: u16 = 1;
+    const A: u16 = 30;
+    const S: u16 = 31;
+    const E: u16 = 18;
+    const C: u16 = 46;
+    const U: u16 = 22;
+    const SPC: u16 = 57;
+    const NINE: u16 = 10;
+    const H: u16 = 35;
+    const BSPC: u16 = 14;
+    const LEFT: u16 = 105;
+    const LCTL: u16 = 29;
+    const RALT: u16 = 100;
+    const LSFT: u16 = 42;
+    const RSFT: u16 = 54;
+    const CAPS: u16 = 58;
+    // above the old 249 limit
+    const FN: u16 = 0x1d0;
+    const BRIGHTNESS_AUTO: u16 = 0x244;
+
+    fn test_key_map() -> HashMap<&'static str, u16> {
+        [
+            ("0", 11),
+            ("1", 2),
+            ("2", 3),
+            ("3", 4),
+            ("9", 10),
+            ("ESC", ESC),
+            ("E", E),
+            ("C", C),
+            ("U", U),
+            ("SPC", SPC),
+            ("A", A),
+            ("S", S),
+            ("H", H),
+            ("BSPC", BSPC),
+            ("LEFT", LEFT),
+            ("LCTL", LCTL),
+            ("RALT", RALT),
+            ("LSFT", LSFT),
+            ("RSFT", RSFT),
+            ("CAPS", CAPS),
+            ("FN", FN),
+            ("BRIGHTNESS_AUTO", BRIGHTNESS_AUTO),
+        ]
+        .iter()
+        .cloned()
+        .collect()
+    }
+
+    fn test_keymaps(config: &str) -> KeyMaps<TestKeyboard, u16, TestEvent> {
+        KeyMaps::new(&test_key_map(), toml::from_str(config).unwrap()).unwrap()
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn send(
+        keymaps: &mut KeyMaps<TestKeyboard, u16, TestEvent>,
+        device: &TestKeyboard,
+        code: u16,
+        value: KeyState,
+        at: Instant,
+    ) {
+        keymaps
+            .send_event_at(&mut TestEvent { code, value }, device, at)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_tap_hold() {
+        use KeyState::*;
+        let config = r###"
+            switch_layout_keys = ['LSFT','RSFT']
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = ['ESC, A, S, CAPS', 'ESC, A, S, TH(ESC, LCTL)']
+            "###;
+        let device = TestKeyboard::default();
+        let mut keymaps = test_keymaps(config);
+        let t = Instant::now();
+
+        // tapped
+        send(&mut keymaps, &device, CAPS, DOWN, t);
+        assert_eq!(device.take(), vec![]);
+        assert_eq!(keymaps.timeout_at(t + ms(50)), Some(ms(150)));
+        send(&mut keymaps, &device, CAPS, UP, t + ms(50));
+        assert_eq!(device.take(), vec![(ESC, DOWN), (ESC, UP)]);
+        assert_eq!(keymaps.timeout_at(t + ms(50)), None);
+
+        // held past tapping_term
+        send(&mut keymaps, &device, CAPS, DOWN, t);
+        keymaps
+            .tick_at(&mut TestEvent { code: 0, value: UP }, &device, t + ms(200))
+            .unwrap();
+        send(&mut keymaps, &device, A, DOWN, t + ms(210));
+        send(&mut keymaps, &device, A, UP, t + ms(220));
+        send(&mut keymaps, &device, CAPS, UP, t + ms(230));
+        assert_eq!(
+            device.take(),
+            vec![(LCTL, DOWN), (A, DOWN), (A, UP), (LCTL, UP)]
+        );
+
+        // another key tapped within tapping_term is still a tap by default, in order
+        send(&mut keymaps, &device, CAPS, DOWN, t);
+        send(&mut keymaps, &device, A, DOWN, t + ms(10));
+        send(&mut keymaps, &device, A, UP, t + ms(20));
+        assert_eq!(device.take(), vec![]);
+        send(&mut keymaps, &device, CAPS, UP, t + ms(30));
+        assert_eq!(
+            device.take(),
+            vec![(ESC, DOWN), (A, DOWN), (A, UP), (ESC, UP)]
+        );
+
+        // but a hold with permissive_hold
+        let mut keymaps = test_keymaps(&format!("{}\n[tap_hold]\npermissive_hold = true", config));
+        send(&mut keymaps, &device, CAPS, DOWN, t);
+        send(&mut keymaps, &device, A, DOWN, t + ms(10));
+        send(&mut keymaps, &device, S, DOWN, t + ms(15));
+        assert_eq!(device.take(), vec![]);
+        send(&mut keymaps, &device, A, UP, t + ms(20));
+        send(&mut keymaps, &device, CAPS, UP, t + ms(30));
+        send(&mut keymaps, &device, S, UP, t + ms(40));
+        assert_eq!(
+            device.take(),
+            vec![
+                (LCTL, DOWN),
+                (A, DOWN),
+                (S, DOWN),
+                (A, UP),
+                (LCTL, UP),
+                (S, UP)
+            ]
+        );
+
+        // and with hold_on_other_key_press as soon as another key is pressed
+        let mut keymaps = test_keymaps(&format!(
+            "{}\n[tap_hold]\nhold_on_other_key_press = true",
+            config
+        ));
+        send(&mut keymaps, &device, CAPS, DOWN, t);
+        send(&mut keymaps, &device, A, DOWN, t + ms(10));
+        assert_eq!(device.take(), vec![(LCTL, DOWN), (A, DOWN)]);
+
+        // a held revert key switches to the revert keymap like pressing it does
+        let mut keymaps = test_keymaps(
+            r###"
+            switch_layout_keys = ['LSFT','RSFT']
+            revert_default_keys = ['LCTL']
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = ['ESC, A, S, CAPS', 'ESC, S, A, TH(ESC, LCTL)']
+            "###,
+        );
+        send(&mut keymaps, &device, CAPS, DOWN, t);
+        keymaps
+            .tick_at(&mut TestEvent { code: 0, value: UP }, &device, t + ms(200))
+            .unwrap();
+        send(&mut keymaps, &device, A, DOWN, t + ms(210));
+        send(&mut keymaps, &device, A, UP, t + ms(220));
+        send(&mut keymaps, &device, CAPS, UP, t + ms(230));
+        send(&mut keymaps, &device, A, DOWN, t + ms(240));
+        send(&mut keymaps, &device, A, UP, t + ms(250));
+        assert_eq!(
+            device.take(),
+            vec![
+                (LCTL, DOWN),
+                (A, DOWN),
+                (A, UP),
+                (LCTL, UP),
+                (S, DOWN),
+                (S, UP)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_layers() {
+        use KeyState::*;
+        let device = TestKeyboard::default();
+        let mut keymaps = test_keymaps(
+            r###"
+            switch_layout_keys = ['LSFT','RSFT']
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = [
+                'ESC,    A,    S,     CAPS',
+                'ESC,    A,    S,     MO(2)',
+                'OSL(3), S,    TG(3), TRNS',
+                'TRNS,   ESC,  TRNS,  TRNS',
+            ]
+            "###,
+        );
+        let t = Instant::now();
+
+        send(&mut keymaps, &device, A, DOWN, t);
+        send(&mut keymaps, &device, A, UP, t);
+        assert_eq!(device.take(), vec![(A, DOWN), (A, UP)]);
+
+        // momentary, released from the layer it was pressed on
+        send(&mut keymaps, &device, CAPS, DOWN, t);
+        send(&mut keymaps, &device, A, DOWN, t);
+        send(&mut keymaps, &device, CAPS, UP, t);
+        send(&mut keymaps, &device, A, UP, t);
+        assert_eq!(device.take(), vec![(S, DOWN), (S, UP)]);
+
+        // one-shot, for the next key only
+        send(&mut keymaps, &device, CAPS, DOWN, t);
+        send(&mut keymaps, &device, ESC, DOWN, t);
+        send(&mut keymaps, &device, ESC, UP, t);
+        send(&mut keymaps, &device, CAPS, UP, t);
+        for _ in 0..2 {
+            send(&mut keymaps, &device, A, DOWN, t);
+            send(&mut keymaps, &device, A, UP, t);
+        }
+        assert_eq!(
+            device.take(),
+            vec![(ESC, DOWN), (ESC, UP), (A, DOWN), (A, UP)]
+        );
+
+        // toggled on, S falls through transparent layer 3 to layer 1
+        send(&mut keymaps, &device, CAPS, DOWN, t);
+        send(&mut keymaps, &device, S, DOWN, t);
+        send(&mut keymaps, &device, S, UP, t);
+        send(&mut keymaps, &device, CAPS, UP, t);
+        for code in &[A, S] {
+            send(&mut keymaps, &device, *code, DOWN, t);
+            send(&mut keymaps, &device, *code, UP, t);
+        }
+        assert_eq!(
+            device.take(),
+            vec![(ESC, DOWN), (ESC, UP), (S, DOWN), (S, UP)]
+        );
+
+        // and toggled back off
+        send(&mut keymaps, &device, CAPS, DOWN, t);
+        send(&mut keymaps, &device, S, DOWN, t);
+        send(&mut keymaps, &device, S, UP, t);
+        send(&mut keymaps, &device, CAPS, UP, t);
+        send(&mut keymaps, &device, A, DOWN, t);
+        send(&mut keymaps, &device, A, UP, t);
+        assert_eq!(device.take(), vec![(A, DOWN), (A, UP)]);
+    }
+
+    #[test]
+    fn test_macros() {
+        use KeyState::*;
+        let device = TestKeyboard::default();
+        let mut keymaps = test_keymaps(
+            r###"
+            switch_layout_keys = ['LSFT','RSFT']
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = ['ESC, A, S, CAPS', 'M(shortcut), M(steps), S, CAPS']
+
+            [macros]
+            shortcut = "LCTL+LSFT+S"
+            steps = "A, DOWN(ESC), DELAY(1), UP(ESC)"
+            "###,
+        );
+        let t = Instant::now();
+
+        send(&mut keymaps, &device, ESC, DOWN, t);
+        send(&mut keymaps, &device, ESC, OTHER, t);
+        send(&mut keymaps, &device, ESC, UP, t);
+        assert_eq!(
+            device.take(),
+            vec![
+                (LCTL, DOWN),
+                (LSFT, DOWN),
+                (S, DOWN),
+                (S, UP),
+                (LSFT, UP),
+                (LCTL, UP)
+            ]
+        );
+
+        // the steps after a delay are played from tick, keys pressed before then wait for them
+        send(&mut keymaps, &device, A, DOWN, t);
+        send(&mut keymaps, &device, A, UP, t);
+        send(&mut keymaps, &device, S, DOWN, t);
+        assert_eq!(device.take(), vec![(A, DOWN), (A, UP), (ESC, DOWN)]);
+        assert_eq!(keymaps.timeout_at(t), Some(ms(1)));
+        keymaps
+            .tick_at(&mut TestEvent { code: 0, value: UP }, &device, t + ms(1))
+            .unwrap();
+        assert_eq!(device.take(), vec![(ESC, UP), (S, DOWN)]);
+        assert_eq!(keymaps.timeout_at(t + ms(1)), None);
+    }
+
+    #[test]
+    fn test_combos() {
+        use KeyState::*;
+        let device = TestKeyboard::default();
+        let mut keymaps = test_keymaps(
+            r###"
+            switch_layout_keys = ['LSFT','RSFT']
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = ['ESC, A, S, CAPS', 'ESC, A, S, CAPS']
+
+            [[combos]]
+            keys = ['A', 'S']
+            send = 'ESC'
+            "###,
+        );
+        let t = Instant::now();
+
+        // pressed together, released with the first key
+        send(&mut keymaps, &device, A, DOWN, t);
+        assert_eq!(keymaps.timeout_at(t + ms(10)), Some(ms(20)));
+        send(&mut keymaps, &device, S, DOWN, t + ms(10));
+        send(&mut keymaps, &device, S, OTHER, t + ms(20));
+        send(&mut keymaps, &device, A, UP, t + ms(30));
+        send(&mut keymaps, &device, S, UP, t + ms(40));
+        assert_eq!(device.take(), vec![(ESC, DOWN), (ESC, UP)]);
+        assert_eq!(keymaps.timeout_at(t + ms(40)), None);
+
+        // too slow
+        send(&mut keymaps, &device, A, DOWN, t);
+        send(&mut keymaps, &device, S, DOWN, t + ms(40));
+        send(&mut keymaps, &device, S, UP, t + ms(50));
+        send(&mut keymaps, &device, A, UP, t + ms(60));
+        assert_eq!(device.take(), vec![(A, DOWN), (S, DOWN), (S, UP), (A, UP)]);
+
+        // other keys or timing out send the pending key on
+        send(&mut keymaps, &device, A, DOWN, t);
+        send(&mut keymaps, &device, CAPS, DOWN, t + ms(10));
+        assert_eq!(device.take(), vec![(A, DOWN), (CAPS, DOWN)]);
+        send(&mut keymaps, &device, S, DOWN, t);
+        keymaps
+            .tick_at(&mut TestEvent { code: 0, value: UP }, &device, t + ms(30))
+            .unwrap();
+        assert_eq!(device.take(), vec![(S, DOWN)]);
+    }
+
+    #[test]
+    fn test_high_codes() {
+        use KeyState::*;
+        let device = TestKeyboard::default();
+        let mut keymaps = test_keymaps(
+            r###"
+            switch_layout_keys = ['FN']
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = [
+                'ESC,    A,               BRIGHTNESS_AUTO, 0,    #766',
+                'ESC,    BRIGHTNESS_AUTO, A,               FN,   #767',
+                'FN,     S,               ^S,              TRNS',
+            ]
+            "###,
+        );
+        let t = Instant::now();
+
+        send(&mut keymaps, &device, A, DOWN, t);
+        send(&mut keymaps, &device, BRIGHTNESS_AUTO, DOWN, t);
+        // not in any keymap, sent unchanged
+        send(&mut keymaps, &device, 0x2fd, DOWN, t);
+        // codes without a name
+        send(&mut keymaps, &device, 0x2fe, DOWN, t);
+        assert_eq!(
+            device.take(),
+            vec![
+                (BRIGHTNESS_AUTO, DOWN),
+                (A, DOWN),
+                (0x2fd, DOWN),
+                (0x2ff, DOWN)
+            ]
+        );
+
+        // a high code works as a switch_layout_key
+        send(&mut keymaps, &device, FN, DOWN, t);
+        send(&mut keymaps, &device, 3, DOWN, t);
+        send(&mut keymaps, &device, FN, UP, t);
+        send(&mut keymaps, &device, ESC, DOWN, t);
+        assert_eq!(device.take(), vec![(FN, DOWN), (FN, UP), (FN, DOWN)]);
+    }
+
+    #[test]
+    fn test_mouse() {
+        use KeyState::*;
+        let device = TestKeyboard::default();
+        let mut keymaps = test_keymaps(
+            r###"
+            switch_layout_keys = []
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = [
+                'ESC,     A,          S,           LCTL',
+                'WHEEL_UP, MOUSE_LEFT, MOUSE_DOWN:3, LCTL',
+            ]
+            "###,
+        );
+        let t = Instant::now();
+
+        send(&mut keymaps, &device, ESC, DOWN, t);
+        send(&mut keymaps, &device, ESC, UP, t);
+        // auto-repeat keeps moving
+        send(&mut keymaps, &device, A, DOWN, t);
+        send(&mut keymaps, &device, A, OTHER, t);
+        send(&mut keymaps, &device, A, UP, t);
+        send(&mut keymaps, &device, S, DOWN, t);
+        send(&mut keymaps, &device, S, UP, t);
+        assert_eq!(device.take(), vec![]);
+        assert_eq!(
+            device.moved.replace(Vec::new()),
+            vec![
+                (MouseMove::WheelUp, 1),
+                (MouseMove::Left, 10),
+                (MouseMove::Left, 10),
+                (MouseMove::Down, 3),
+            ]
+        );
+
+        assert_eq!(
+            keymaps.describe_keymap(&test_key_map(), 1),
+            vec![
+                ("ESC".to_string(), "WHEEL_UP".to_string(), String::new()),
+                ("A".to_string(), "MOUSE_LEFT".to_string(), String::new()),
+                ("S".to_string(), "MOUSE_DOWN:3".to_string(), String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_key_state() {
+        use KeyState::*;
+        let device = TestKeyboard::default();
+        let mut keymaps = test_keymaps(
+            r###"
+            switch_layout_keys = []
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = ['ESC, A', 'S:ESC, *S']
+            "###,
+        );
+        let t = Instant::now();
+
+        // caps lock turned on somewhere else, caps lock modifies S so it is shifted
+        keymaps.set_caps_lock(true, &device);
+        send(&mut keymaps, &device, A, DOWN, t);
+        send(&mut keymaps, &device, A, UP, t);
+        assert_eq!(
+            device.take(),
+            vec![(LSFT, DOWN), (S, DOWN), (S, UP), (LSFT, UP)]
+        );
+
+        keymaps.set_caps_lock(false, &device);
+        send(&mut keymaps, &device, A, DOWN, t);
+        send(&mut keymaps, &device, A, UP, t);
+        assert_eq!(device.take(), vec![(S, DOWN), (S, UP)]);
+        // shift was already held when the keyboard was grabbed, so the shifted half is sent, unshifted
+        keymaps.seed_key_state(&[LSFT, CAPS], false, &device);
+        send(&mut keymaps, &device, ESC, DOWN, t);
+        send(&mut keymaps, &device, ESC, UP, t);
+        assert_eq!(
+            device.take(),
+            vec![(LSFT, UP), (ESC, DOWN), (ESC, UP), (LSFT, DOWN)]
+        );
+        send(&mut keymaps, &device, LSFT, UP, t);
+        send(&mut keymaps, &device, ESC, DOWN, t);
+        send(&mut keymaps, &device, ESC, UP, t);
+        assert_eq!(device.take(), vec![(LSFT, UP), (S, DOWN), (S, UP)]);
+    }
+
+    #[test]
+    fn test_repeat() {
+        use KeyState::*;
+        let device = TestKeyboard::default();
+        let mut keymaps = test_keymaps(
+            r###"
+            switch_layout_keys = []
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = ['ESC, A, LSFT', 'ESC, ^S, LSFT']
+            [repeat]
+            delay_ms = 100
+            period_ms = 50
+            no_repeat = ['LSFT']
+            "###,
+        );
+        let t = Instant::now();
+
+        send(&mut keymaps, &device, A, DOWN, t);
+        assert_eq!(device.take(), vec![(LSFT, DOWN), (S, DOWN)]);
+        // the keyboard's own repeat is dropped, ours comes after delay_ms, shift is still inverted from the press
+        send(&mut keymaps, &device, A, OTHER, t + ms(30));
+        assert_eq!(device.take(), vec![]);
+        assert_eq!(keymaps.timeout_at(t), Some(ms(100)));
+        keymaps
+            .tick_at(&mut TestEvent { code: 0, value: UP }, &device, t + ms(100))
+            .unwrap();
+        assert_eq!(device.take(), vec![(S, OTHER)]);
+        assert_eq!(keymaps.timeout_at(t + ms(100)), Some(ms(50)));
+
+        // a no_repeat key doesn't repeat or stop the repeat
+        send(&mut keymaps, &device, LSFT, DOWN, t + ms(120));
+        keymaps
+            .tick_at(&mut TestEvent { code: 0, value: UP }, &device, t + ms(150))
+            .unwrap();
+        assert_eq!(device.take(), vec![(LSFT, DOWN), (S, OTHER)]);
+        send(&mut keymaps, &device, LSFT, UP, t + ms(160));
+        send(&mut keymaps, &device, A, UP, t + ms(170));
+        assert_eq!(device.take(), vec![(LSFT, UP), (S, UP), (LSFT, UP)]);
+        assert_eq!(keymaps.timeout_at(t + ms(170)), None);
+
+        // shift goes down once with the press and up once with the release, however many repeats between
+        send(&mut keymaps, &device, A, DOWN, t);
+        for tick in [100, 150] {
+            keymaps
+                .tick_at(&mut TestEvent { code: 0, value: UP }, &device, t + ms(tick))
+                .unwrap();
+        }
+        send(&mut keymaps, &device, A, UP, t + ms(170));
+        assert_eq!(
+            device.take(),
+            vec![
+                (LSFT, DOWN),
+                (S, DOWN),
+                (S, OTHER),
+                (S, OTHER),
+                (S, UP),
+                (LSFT, UP)
+            ]
+        );
+
+        // where tick is never called the keyboard's own repeat is passed on
+        let device = TestKeyboard {
+            no_tick: true,
+            ..TestKeyboard::default()
+        };
+        send(&mut keymaps, &device, A, DOWN, t);
+        send(&mut keymaps, &device, A, OTHER, t + ms(30));
+        send(&mut keymaps, &device, A, UP, t + ms(40));
+        assert_eq!(
+            device.take(),
+            vec![(LSFT, DOWN), (S, DOWN), (S, OTHER), (S, UP), (LSFT, UP)]
+        );
+        assert_eq!(keymaps.timeout_at(t + ms(40)), None);
+    }
 
-pub trait DeviceIds {
-    fn bustype(&self) -> Option<u16>;
-    fn vendor(&self) -> Option<u16>;
-    fn product(&self) -> Option<u16>;
-    fn version(&self) -> Option<u16>;
-}
+    #[test]
+    fn test_one_shot() {
+        use KeyState::*;
+        let device = TestKeyboard::default();
+        let mut keymaps = test_keymaps(
+            r###"
+            switch_layout_keys = []
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = ['ESC, A, LSFT, LCTL', 'ESC, ^S, LSFT, LCTL']
+            [one_shot]
+            LSFT = 500
+            LCTL = 0
+            "###,
+        );
+        let t = Instant::now();
 
-#[derive(Debug, Default)]
-#[cfg_attr(feature = "toml_serde", derive(serde::Deserialize))]
-#[cfg_attr(feature = "toml_serde", serde(deny_unknown_fields))]
-pub struct DeviceMatcher {
-    pub bustype: Option<u16>,
-    pub vendor: Option<u16>,
-    pub product: Option<u16>,
-    pub version: Option<u16>,
-}
+        // tapped, shift stays down for the next key only
+        send(&mut keymaps, &device, LSFT, DOWN, t);
+        send(&mut keymaps, &device, LSFT, UP, t);
+        assert_eq!(device.take(), vec![(LSFT, DOWN)]);
+        send(&mut keymaps, &device, ESC, DOWN, t);
+        send(&mut keymaps, &device, ESC, UP, t);
+        send(&mut keymaps, &device, A, DOWN, t);
+        assert_eq!(
+            device.take(),
+            vec![(ESC, DOWN), (LSFT, UP), (ESC, UP), (LSFT, DOWN), (S, DOWN)]
+        );
+        send(&mut keymaps, &device, A, UP, t);
+        device.take();
 
-#[derive(Debug)]
-#[cfg_attr(feature = "toml_serde", derive(serde::Deserialize))]
-#[cfg_attr(feature = "toml_serde", serde(deny_unknown_fields))]
-pub struct DeviceMatchers {
-    #[cfg_attr(feature = "toml_serde", serde(default))]
-    grab: Vec<DeviceMatcher>,
-    #[cfg_attr(feature = "toml_serde", serde(default = "default_skip"))]
-    skip: Vec<DeviceMatcher>,
-}
+        // held like a normal modifier
+        send(&mut keymaps, &device, LSFT, DOWN, t);
+        send(&mut keymaps, &device, ESC, DOWN, t);
+        send(&mut keymaps, &device, ESC, UP, t);
+        send(&mut keymaps, &device, LSFT, UP, t);
+        assert_eq!(
+            device.take(),
+            vec![(LSFT, DOWN), (ESC, DOWN), (ESC, UP), (LSFT, UP)]
+        );
 
-impl Default for DeviceMatchers {
-    fn default() -> Self {
-        Self {
-            grab: Default::default(),
-            skip: default_skip(),
+        // released after its timeout
+        send(&mut keymaps, &device, LSFT, DOWN, t);
+        send(&mut keymaps, &device, LSFT, UP, t);
+        assert_eq!(keymaps.timeout_at(t), Some(ms(500)));
+        keymaps
+            .tick_at(&mut TestEvent { code: 0, value: UP }, &device, t + ms(500))
+            .unwrap();
+        assert_eq!(device.take(), vec![(LSFT, DOWN), (LSFT, UP)]);
+
+        // tapped twice locks it, with no timeout, until tapped again
+        for _ in 0..2 {
+            send(&mut keymaps, &device, LCTL, DOWN, t);
+            send(&mut keymaps, &device, LCTL, UP, t);
         }
+        send(&mut keymaps, &device, ESC, DOWN, t);
+        send(&mut keymaps, &device, ESC, UP, t);
+        assert_eq!(keymaps.timeout_at(t), None);
+        send(&mut keymaps, &device, LCTL, DOWN, t);
+        send(&mut keymaps, &device, LCTL, UP, t);
+        assert_eq!(
+            device.take(),
+            vec![(LCTL, DOWN), (ESC, DOWN), (ESC, UP), (LCTL, UP)]
+        );
+
+        // a key held back by a tap-hold key or combo keeps the modifier down until it is sent
+        let mut keymaps = test_keymaps(
+            r###"
+            switch_layout_keys = []
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = ['ESC, A, S, H, LSFT', 'ESC, TH(A, LCTL), S, H, LSFT']
+            [[combos]]
+            keys = ['S', 'H']
+            send = 'ESC'
+            [one_shot]
+            LSFT = 100
+            "###,
+        );
+        send(&mut keymaps, &device, LSFT, DOWN, t);
+        send(&mut keymaps, &device, LSFT, UP, t);
+        send(&mut keymaps, &device, A, DOWN, t);
+        // waiting on the tap-hold key, not the one-shot timeout
+        assert_eq!(keymaps.timeout_at(t), Some(ms(200)));
+        send(&mut keymaps, &device, A, UP, t + ms(150));
+        assert_eq!(
+            device.take(),
+            vec![(LSFT, DOWN), (A, DOWN), (LSFT, UP), (A, UP)]
+        );
+
+        send(&mut keymaps, &device, LSFT, DOWN, t);
+        send(&mut keymaps, &device, LSFT, UP, t);
+        send(&mut keymaps, &device, A, DOWN, t);
+        keymaps
+            .tick_at(&mut TestEvent { code: 0, value: UP }, &device, t + ms(200))
+            .unwrap();
+        send(&mut keymaps, &device, A, UP, t + ms(250));
+        assert_eq!(
+            device.take(),
+            vec![(LSFT, DOWN), (LCTL, DOWN), (LSFT, UP), (LCTL, UP)]
+        );
+
+        send(&mut keymaps, &device, LSFT, DOWN, t);
+        send(&mut keymaps, &device, LSFT, UP, t);
+        send(&mut keymaps, &device, S, DOWN, t);
+        send(&mut keymaps, &device, H, DOWN, t);
+        send(&mut keymaps, &device, S, UP, t);
+        send(&mut keymaps, &device, H, UP, t);
+        assert_eq!(
+            device.take(),
+            vec![(LSFT, DOWN), (ESC, DOWN), (LSFT, UP), (ESC, UP)]
+        );
+
+        // not a combo after all, sent as pressed once the combo is given up on
+        send(&mut keymaps, &device, LSFT, DOWN, t);
+        send(&mut keymaps, &device, LSFT, UP, t);
+        send(&mut keymaps, &device, S, DOWN, t);
+        send(&mut keymaps, &device, S, UP, t);
+        assert_eq!(
+            device.take(),
+            vec![(LSFT, DOWN), (S, DOWN), (LSFT, UP), (S, UP)]
+        );
     }
-}
 
-pub fn default_skip() -> Vec<DeviceMatcher> {
-    vec![
-        // 0x1050 is Yubico
-        DeviceMatcher {
-            vendor: Some(0x1050),
-            ..Default::default()
-        },
-    ]
-}
+    #[test]
+    fn test_mod_rules() {
+        use KeyState::*;
+        let device = TestKeyboard::default();
+        let config = r###"
+            switch_layout_keys = []
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = ['ESC, A, H, LCTL, RALT, LSFT', 'ESC, S, H, LCTL, RALT, LSFT']
+            [[mod_rules]]
+            key = 'H'
+            mods = ['RALT']
+            send = 'LEFT'
+            consume = true
+            [[mod_rules]]
+            key = 'H'
+            mods = ['CTRL']
+            send = 'BSPC'
+            only = true
+            consume = true
+            [[mod_rules]]
+            key = 'A'
+            mods = ['SHIFT']
+            send = 'E'
+            "###;
+        let mut keymaps = test_keymaps(config);
+        let t = Instant::now();
 
-#[inline]
-fn um(rule: Option<u16>, data: Option<u16>) -> bool {
-    rule.is_none() || rule == data
-}
+        // consumed, let go around each press and back down after
+        send(&mut keymaps, &device, RALT, DOWN, t);
+        for _ in 0..2 {
+            send(&mut keymaps, &device, H, DOWN, t);
+            send(&mut keymaps, &device, H, UP, t);
+        }
+        send(&mut keymaps, &device, RALT, UP, t);
+        assert_eq!(
+            device.take(),
+            vec![
+                (RALT, DOWN),
+                (RALT, UP),
+                (LEFT, DOWN),
+                (LEFT, UP),
+                (RALT, DOWN),
+                (RALT, UP),
+                (LEFT, DOWN),
+                (LEFT, UP),
+                (RALT, DOWN),
+                (RALT, UP)
+            ]
+        );
 
-impl DeviceMatcher {
-    pub fn matches(&self, device: &dyn DeviceIds) -> bool {
-        um(self.bustype, device.bustype())
-            && um(self.vendor, device.vendor())
-            && um(self.product, device.product())
-            && um(self.version, device.version())
+        // not pressed again if it was let go first
+        send(&mut keymaps, &device, RALT, DOWN, t);
+        send(&mut keymaps, &device, H, DOWN, t);
+        send(&mut keymaps, &device, RALT, UP, t);
+        send(&mut keymaps, &device, H, UP, t);
+        assert_eq!(
+            device.take(),
+            vec![
+                (RALT, DOWN),
+                (RALT, UP),
+                (LEFT, DOWN),
+                (RALT, UP),
+                (LEFT, UP)
+            ]
+        );
+
+        // only when ctrl is the only modifier held
+        send(&mut keymaps, &device, LCTL, DOWN, t);
+        send(&mut keymaps, &device, H, DOWN, t);
+        send(&mut keymaps, &device, H, UP, t);
+        send(&mut keymaps, &device, LSFT, DOWN, t);
+        send(&mut keymaps, &device, H, DOWN, t);
+        send(&mut keymaps, &device, H, UP, t);
+        send(&mut keymaps, &device, LSFT, UP, t);
+        send(&mut keymaps, &device, LCTL, UP, t);
+        assert_eq!(
+            device.take(),
+            vec![
+                (LCTL, DOWN),
+                (LCTL, UP),
+                (BSPC, DOWN),
+                (BSPC, UP),
+                (LCTL, DOWN),
+                (LSFT, DOWN),
+                (H, DOWN),
+                (H, UP),
+                (LSFT, UP),
+                (LCTL, UP)
+            ]
+        );
+
+        // not consumed, shift stays down, and without it the keymap applies
+        send(&mut keymaps, &device, LSFT, DOWN, t);
+        send(&mut keymaps, &device, A, DOWN, t);
+        send(&mut keymaps, &device, A, UP, t);
+        send(&mut keymaps, &device, LSFT, UP, t);
+        send(&mut keymaps, &device, A, DOWN, t);
+        send(&mut keymaps, &device, A, UP, t);
+        assert_eq!(
+            device.take(),
+            vec![
+                (LSFT, DOWN),
+                (E, DOWN),
+                (E, UP),
+                (LSFT, UP),
+                (S, DOWN),
+                (S, UP)
+            ]
+        );
+
+        assert_eq!(
+            config_error(&config.replace("'BSPC'", "'TH(A, LCTL)'")),
+            "invalid config: mod_rules[1].send: can't be a tap-hold, dead or transparent key: TH(A, LCTL)"
+        );
     }
-}
 
-impl DeviceMatchers {
-    pub fn grab(&self, device: &dyn DeviceIds) -> bool {
-        // we should grab this device if grab is empty or any single entry matches
-        (self.grab.is_empty() || self.grab.iter().any(|d| d.matches(device)))
-            // and skip is empty or no skip entry matches
-            && (self.skip.is_empty() || !self.skip.iter().any(|d| d.matches(device)))
+    #[test]
+    fn test_unicode() {
+        use KeyState::*;
+        let device = TestKeyboard::default();
+        let config = r###"
+            switch_layout_keys = []
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = ['ESC, A, S, LSFT', 'ESC, U+00E9:É, →, LSFT']
+            "###;
+        let mut keymaps = test_keymaps(config);
+        let t = Instant::now();
+        let tap = |code| vec![(code, DOWN), (code, UP)];
+        let ctrl_shift_u = vec![
+            (LCTL, DOWN),
+            (LSFT, DOWN),
+            (U, DOWN),
+            (U, UP),
+            (LSFT, UP),
+            (LCTL, UP),
+        ];
+
+        send(&mut keymaps, &device, A, DOWN, t);
+        send(&mut keymaps, &device, A, UP, t);
+        assert_eq!(
+            device.take(),
+            [ctrl_shift_u.clone(), tap(E), tap(NINE), tap(SPC)].concat()
+        );
+        // shifted, with shift let go while typing it
+        send(&mut keymaps, &device, LSFT, DOWN, t);
+        send(&mut keymaps, &device, A, DOWN, t);
+        assert_eq!(
+            device.take(),
+            [
+                vec![(LSFT, DOWN), (LSFT, UP)],
+                ctrl_shift_u,
+                tap(C),
+                tap(NINE),
+                tap(SPC),
+                vec![(LSFT, DOWN)]
+            ]
+            .concat()
+        );
+
+        let mut keymaps = test_keymaps(&format!(
+            "{}\n[unicode]\nmethod = 'compose'\ncompose_key = 'CAPS'\ncompose = {{ 'é' = 'E', 'É' = 'LSFT+E', '→' = 'S, A' }}",
+            config
+        ));
+        send(&mut keymaps, &device, S, DOWN, t);
+        assert_eq!(device.take(), [tap(CAPS), tap(S), tap(A)].concat());
+
+        assert_eq!(
+            config_error(&format!(
+                "{}\n[unicode]\nmethod = 'compose'\ncompose_key = 'CAPS'",
+                config
+            )),
+            "keymap 1 line 1 column 6: no unicode.compose sequence for U+00E9: U+00E9:É\nkeymap 1 line 1 column 6: no unicode.compose sequence for U+00C9: U+00E9:É\nkeymap 1 line 1 column 16: no unicode.compose sequence for U+2192: →"
+        );
     }
-}
 
-#[cfg(feature = "toml_serde")]
-#[derive(serde::Deserialize, Debug)]
-#[serde(deny_unknown_fields)]
-pub struct KeymapConfig {
-    switch_layout_keys: Vec<String>,
-    revert_default_key: Option<String>,
-    revert_default_keys: Option<Vec<String>>,
-    revert_keymap_index: usize,
-    default_keymap_index: usize,
-    keymaps: Vec<String>,
-    #[serde(default)]
-    devices: DeviceMatchers,
-}
+    #[test]
+    fn test_compose() {
+        use KeyState::*;
+        let device = TestKeyboard::default();
+        let config = r###"
+            switch_layout_keys = []
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = ['ESC, A, S, LSFT, E', 'DEAD_ACUTE, A, S, LSFT, E']
+            [compose.DEAD_ACUTE]
+            E = 'é:É'
+            'S, S' = 'A'
+            "###;
+        let mut keymaps = test_keymaps(config);
+        let t = Instant::now();
+        let tap = |code| vec![(code, DOWN), (code, UP)];
+        let ctrl_shift_u = vec![
+            (LCTL, DOWN),
+            (LSFT, DOWN),
+            (U, DOWN),
+            (U, UP),
+            (LSFT, UP),
+            (LCTL, UP),
+        ];
 
-#[cfg(feature = "toml_serde")]
-fn parse_cfg<P: AsRef<Path>>(path: P) -> Result<KeymapConfig> {
-    use std::io::Read;
-    let mut f = std::fs::File::open(path)?;
-    let mut input = String::new();
-    f.read_to_string(&mut input)?;
-    toml::from_str(&input).map_err(|e| crate::Error::Toml(e))
-}
+        for (code, value) in [(ESC, DOWN), (ESC, UP), (E, DOWN), (E, UP)] {
+            send(&mut keymaps, &device, code, value, t);
+        }
+        assert_eq!(
+            device.take(),
+            [ctrl_shift_u.clone(), tap(E), tap(NINE), tap(SPC)].concat()
+        );
 
-#[cfg(not(feature = "toml_serde"))]
-#[derive(Debug)]
-pub struct KeymapConfig {
-    switch_layout_keys: Vec<&'static str>,
-    revert_default_key: Option<&'static str>,
-    revert_default_keys: Option<Vec<&'static str>>,
-    revert_keymap_index: usize,
-    default_keymap_index: usize,
-    keymaps: Vec<&'static str>,
-    devices: DeviceMatchers,
-}
+        // shift held for the key after the dead key
+        for (code, value) in [(ESC, DOWN), (ESC, UP), (LSFT, DOWN), (E, DOWN), (E, UP)] {
+            send(&mut keymaps, &device, code, value, t);
+        }
+        send(&mut keymaps, &device, LSFT, UP, t);
+        assert_eq!(
+            device.take(),
+            [
+                vec![(LSFT, DOWN), (LSFT, UP)],
+                ctrl_shift_u,
+                tap(C),
+                tap(NINE),
+                tap(SPC),
+                vec![(LSFT, DOWN), (LSFT, UP)]
+            ]
+            .concat()
+        );
 
-#[cfg(not(feature = "toml_serde"))]
-impl Default for KeymapConfig {
-    fn default() -> Self {
-        KeymapConfig {
-            switch_layout_keys: vec!["LEFTSHIFT", "RIGHTSHIFT"],
+        // longer sequences, with keys for the result
+        for (code, value) in [
+            (ESC, DOWN),
+            (ESC, UP),
+            (S, DOWN),
+            (S, UP),
+            (S, DOWN),
+            (S, UP),
+        ] {
+            send(&mut keymaps, &device, code, value, t);
+        }
+        assert_eq!(device.take(), tap(A));
 
-            // pressing any of these keys reverts to the index specified in revert_keymap_index for only the duration of the pressing
-            // used so QWERTY shortcuts like Ctrl+C still work
-            revert_default_keys: Some(vec!["LCTL", "LGUI", "LALT"]),
-            revert_keymap_index: 0,
+        // not a sequence, typed like there was no dead key
+        for (code, value) in [
+            (ESC, DOWN),
+            (ESC, UP),
+            (S, DOWN),
+            (S, UP),
+            (A, DOWN),
+            (A, UP),
+        ] {
+            send(&mut keymaps, &device, code, value, t);
+        }
+        assert_eq!(device.take(), [tap(S), tap(A)].concat());
 
-            // this is the default index to use when the program first starts
-            // in this case, 2 means Unix Programmer Dvorak
-            default_keymap_index: 2,
+        // a key starting no sequence goes on alone, the dead key types nothing itself
+        for (code, value) in [(ESC, DOWN), (ESC, UP), (A, DOWN), (A, UP)] {
+            send(&mut keymaps, &device, code, value, t);
+        }
+        assert_eq!(device.take(), tap(A));
 
-            // these are the keymaps available, you can add as many as you want or re-order them, just be aware the mapping is
-            // always done from the first one to all subsequent ones, so you probably want to leave QWERTY or similar up top
-            keymaps: vec![
-                // default key layout, QWERTY in this case
-                r###"
-                ESC, F1,  F2,  F3,  F4,  F5,  F6,  F7,  F8,  F9,  F10, F11, F12,           PSCR,SLCK,BRK,
-                GRV, 1,   2,   3,   4,   5,   6,   7,   8,   9,   0,   MINS,EQL, BSPC,     INS, HOME,PGUP,    NLCK,PSLS,PAST,PMNS,
-                TAB, Q,   W,   E,   R,   T,   Y,   U,   I,   O,   P,   LBRC,RBRC,BSLS,     DEL, END, PGDN,    P7,  P8,  P9,
-                CAPS,A,   S,   D,   F,   G,   H,   J,   K,   L,   SCLN,QUOT,     ENT,                         P4,  P5,  P6,  PPLS,
-                LSFT,Z,   X,   C,   V,   B,   N,   M,   COMM,DOT, SLSH,          RSFT,          UP,           P1,  P2,  P3,
-                LCTL,LGUI,LALT,          SPC,                     RALT,RGUI,APP, RCTL,     LEFT,DOWN,RGHT,    P0,       PDOT,PENT
-                "###,
-                // Dvorak http://en.wikipedia.org/wiki/Dvorak_Simplified_Keyboard
-                // https://www.moparisthebest.com/kbs/standard-dvorak-QwertySecondary.svg
-                r###"
-                ESC, F1,  F2,  F3,  F4,  F5,  F6,  F7,  F8,  F9,  F10, F11, F12,           PSCR,SLCK,BRK,
-                GRV, 1,   2,   3,   4,   5,   6,   7,   8,   9,   0,   LBRC,RBRC,BSPC,     INS, HOME,PGUP,    NLCK,PSLS,PAST,PMNS,
-                TAB, QUOT,COMM,DOT, P,   Y,   F,   G,   C,   R,   L,   SLSH,EQL, BSLS,     DEL, END, PGDN,    P7,  P8,  P9,
-                CAPS,A,   O,   E,   U,   I,   D,   H,   T,   N,   S,   MINS,     ENT,                         P4,  P5,  P6,  PPLS,
-                LSFT,SCLN,Q,   J,   K,   X,   B,   M,   W,   V,   Z,             RSFT,          UP,           P1,  P2,  P3,
-                LCTL,LGUI,LALT,          SPC,                     RALT,RGUI,APP, RCTL,     LEFT,DOWN,RGHT,    P0,       PDOT,PENT
-                "###,
-                // Unix Programmer Dvorak - for unix developers who are switching from dvorak
-                // https://www.moparisthebest.com/kbs/programmer-dvorak-NoSecondary-NumpadStandard-NoSwap-StandardNums-SwapAt-SwapPipe.svg
+        assert_eq!(
+            config_error(
                 r###"
-                ESC,      F1,    F2,     F3,      F4,      F5,   F6,    F7,   F8,   F9,     F10,    F11,   F12,                 PSCR,SLCK,BRK,
-                *^4:*^GRV,  *^7:*1,  *LBRC:*2, *^LBRC:*3, *^RBRC:*4, *^9:*5, *^2:*6,  *^8:*7, *^0:*8, *^EQL:*9, *RBRC:*0, *^1:*^5, *^3:*GRV, BSPC,        INS, HOME,PGUP,    NLCK,   *PSLS:*^9, *PAST:*^0,   *PMNS:*^4,
-                TAB,      *QUOT,  *COMM,   *DOT,     P,       Y,    F,     G,    C,    R,      L,      *SLSH,  *EQL:*^6, *^BSLS,  DEL, END, PGDN,    *P7:^A,  *P8:^B,   *P9:^C,
-                CAPS,     A,     O,      E,       U,       I,    D,     H,    T,    N,      S,      *MINS,          ENT,                            *P4:^D,  *P5:^E,   *P6:^F,     *PPLS:*COMM,
-                LSFT,     *SCLN,  Q,      J,       K,       X,    B,     M,    W,    V,      Z,                     RSFT,             UP,           *P1:*EQL, *P2:X,    *P3:*^SCLN,
-                LCTL,     LGUI,  LALT,                     SPC,                             RALT,   RGUI,  APP,    RCTL,        LEFT,DOWN,RGHT,    *P0:*BSLS,         *PDOT:*SCLN, PENT
-                "###,
-            ],
-            revert_default_key: None, // use revert_default_keys instead
-            devices: Default::default(),
+            switch_layout_keys = []
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = ['ESC, A', 'DEAD_GRAVE, A']
+            "###
+            ),
+            "keymap 1 line 1 column 1: unknown dead key: DEAD_GRAVE"
+        );
+    }
+
+    fn config_error(config: &str) -> String {
+        match KeyMaps::<TestKeyboard, u16, TestEvent>::with_profiles(
+            &test_key_map(),
+            &toml::from_str(config).unwrap(),
+        ) {
+            Ok(_) => panic!("config should not parse"),
+            Err(e) => e.to_string(),
         }
     }
-}
 
-#[cfg(test)]
-mod test {
-    use libc::input_id;
+    #[test]
+    fn test_config_errors() {
+        assert_eq!(
+            config_error(
+                r###"
+            switch_layout_keys = []
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = ['ESC, A, S', 'ESC, S, A']
+            [repeat]
+            period_ms = 0
+            no_repeat = ['NOPE']
+            "###
+            ),
+            "invalid config: repeat.period_ms must be more than 0\ninvalid config: repeat.no_repeat: unknown key: NOPE"
+        );
+        assert_eq!(
+            config_error(
+                r###"
+            switch_layout_keys = []
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = ['ESC, A, S', """ESC,
+            A,  NOPE"""]
+            "###
+            ),
+            "keymap 1 line 2 column 17: unknown key: NOPE"
+        );
+        assert_eq!(
+            config_error(
+                r###"
+            switch_layout_keys = []
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = ['ESC, A, S', 'ESC, #A, #99999']
+            "###
+            ),
+            "keymap 1 line 1 column 6: invalid key code: #A\nkeymap 1 line 1 column 10: invalid key code: #99999"
+        );
+        assert_eq!(
+            config_error(
+                r###"
+            switch_layout_keys = []
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = ['ESC, A, S', 'ESC, A, WHEEL_UP:0']
+            "###
+            ),
+            "keymap 1 line 1 column 9: mouse move amount must be a positive number: WHEEL_UP:0"
+        );
+        assert_eq!(
+            config_error(
+                r###"
+            switch_layout_keys = []
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = ['ESC, A, S', 'ESC, A, MO(2)']
+            "###
+            ),
+            "keymap 1 line 1 column 9: layer (2) must be less than keymaps length (2): MO(2)"
+        );
+        assert_eq!(
+            config_error(
+                r###"
+            switch_layout_keys = []
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = ['ESC, A, S', 'ESC, A, TH(A, S']
+            "###
+            ),
+            "keymap 1 line 1 column 9: function missing ): TH(A, S"
+        );
+        assert_eq!(
+            config_error(
+                r###"
+            switch_layout_keys = []
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = ['ESC, A', 'ESC, A, S']
+            "###
+            ),
+            "keymap 1 line 1 column 9: all keymaps must be the same length, keymap index 0 length: 2, index 1 length: 3: S"
+        );
+        assert_eq!(
+            config_error(
+                r###"
+            switch_layout_keys = []
+            revert_keymap_index = 0
+            default_keymap_index = 2
+            keymaps = ['ESC, A', 'ESC, A']
+            "###
+            ),
+            "invalid config: default_keymap_index (2) and revert_keymap_index (0) must be less than keymaps length (2)"
+        );
+        assert_eq!(
+            config_error(
+                r###"
+            switch_layout_keys = ['NOPE']
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = ['ESC, A', 'ESC, B']
+            "###
+            ),
+            "keymap 1 line 1 column 6: unknown key: B\ninvalid config: switch_layout_keys: unknown key: NOPE"
+        );
+    }
 
-    use super::*;
+    #[test]
+    fn test_describe_keymap() {
+        let keymaps = test_keymaps(
+            r###"
+            switch_layout_keys = []
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = ['ESC, A, S, CAPS', 'ESC, S, A, CAPS', 'ESC, *^A:S, TH(A, LCTL), MO(1)']
+            "###,
+        );
+        let key_map = test_key_map();
+        let describe =
+            |index| -> Vec<(String, String, String)> { keymaps.describe_keymap(&key_map, index) };
+        let row = |base: &str, mapped: &str, flags: &str| {
+            (base.to_string(), mapped.to_string(), flags.to_string())
+        };
+        assert_eq!(keymaps.keymaps_len(), 3);
+        assert_eq!(describe(1), vec![row("A", "S", ""), row("S", "A", "")]);
+        assert_eq!(
+            describe(2),
+            vec![
+                row("A", "*^A:S", "caps lock modifies, shift inverted"),
+                row("S", "TH(A, LCTL)", ""),
+                row("CAPS", "MO(1)", ""),
+            ]
+        );
+    }
 
     #[test]
     fn test_device_matchers() {
@@ -662,6 +4217,9 @@ mod test {
         let devices: DeviceMatchers = toml::from_str("").unwrap();
         assert!(devices.grab.is_empty());
         assert_eq!(devices.skip.len(), 1);
+        assert_eq!(devices.required_keys(), ["W", "A", "S", "D"]);
+        assert_eq!(devices.forbidden_keys(), ["BTN_LEFT"]);
+        assert_eq!(devices.required_events(), ["EV_KEY"]);
         let yubi = input_id {
             vendor: 0x1050,
             product: 0x0406,
@@ -706,5 +4264,198 @@ mod test {
         assert!(!devices.grab(&yubi2));
         assert!(!devices.grab(&ergosnm));
         assert!(devices.grab(&rando));
+        assert!(!devices.always_grab(&ergosnm));
+        let devices: DeviceMatchers = toml::from_str(
+            r###"
+            # a macro pad without WASD
+            required_keys = []
+            forbidden_keys = []
+            [[always_grab]]
+            vendor = 0x5a69
+            "###,
+        )
+        .unwrap();
+        assert!(devices.required_keys().is_empty());
+        assert!(devices.always_grab(&ergosnm));
+        assert!(!devices.always_grab(&rando));
+    }
+
+    struct TestDeviceIds {
+        name: &'static str,
+        uniq: Option<&'static str>,
+    }
+
+    impl DeviceIds for TestDeviceIds {
+        fn bustype(&self) -> Option<u16> {
+            Some(5)
+        }
+
+        fn vendor(&self) -> Option<u16> {
+            Some(0x4c)
+        }
+
+        fn product(&self) -> Option<u16> {
+            Some(0x267)
+        }
+
+        fn version(&self) -> Option<u16> {
+            Some(1)
+        }
+
+        fn name(&self) -> Option<&str> {
+            Some(self.name)
+        }
+
+        fn uniq(&self) -> Option<&str> {
+            self.uniq
+        }
+    }
+
+    #[test]
+    fn test_device_string_matchers() {
+        assert!(glob_matches("", ""));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("*Keyboard*", "Apple Magic Keyboard"));
+        assert!(glob_matches("Apple*Keyboard", "Apple Magic Keyboard"));
+        assert!(glob_matches("a?c", "abc"));
+        assert!(glob_matches("*a*b", "xaxxab"));
+        assert!(!glob_matches("a?c", "ac"));
+        assert!(!glob_matches("*Mouse*", "Apple Magic Keyboard"));
+        assert!(!glob_matches("Apple", "Apple Magic Keyboard"));
+
+        let bluetooth = TestDeviceIds {
+            name: "Magic Keyboard",
+            uniq: Some("aa:bb:cc:dd:ee:ff"),
+        };
+        let usb = TestDeviceIds {
+            name: "Magic Keyboard",
+            uniq: None,
+        };
+        let devices: DeviceMatchers = toml::from_str(
+            r###"
+            [[grab]]
+            name = "*Keyboard"
+            uniq = "aa:bb:*"
+            "###,
+        )
+        .unwrap();
+        assert!(devices.grab(&bluetooth));
+        assert!(!devices.grab(&usb));
+        // there is no phys, so this can't match
+        let devices: DeviceMatchers = toml::from_str(
+            r###"
+            [[grab]]
+            phys = "*"
+            "###,
+        )
+        .unwrap();
+        assert!(!devices.grab(&bluetooth));
+    }
+
+    #[test]
+    fn test_device_profiles() {
+        let ergosnm = input_id {
+            vendor: 0x5a69,
+            product: 0xe200,
+            bustype: 0,
+            version: 0,
+        };
+        let laptop = input_id {
+            vendor: 1,
+            product: 2,
+            bustype: 3,
+            version: 4,
+        };
+        let trackball = input_id {
+            vendor: 7,
+            product: 0,
+            bustype: 0,
+            version: 0,
+        };
+        let config: KeymapConfig = toml::from_str(
+            r###"
+            switch_layout_keys = []
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = ['ESC, A, S', 'ESC, S, A']
+
+            [profiles.ergo]
+            pass_through = true
+
+            [profiles.laptop]
+            default_keymap_index = 0
+
+            [profiles.trackball]
+            scroll_button = 'S'
+
+            [[devices.always_grab]]
+            vendor = 7
+            profile = 'trackball'
+
+            [[devices.grab]]
+            vendor = 0x5a69
+            profile = 'ergo'
+
+            [[devices.grab]]
+            vendor = 1
+            profile = 'laptop'
+            "###,
+        )
+        .unwrap();
+        assert_eq!(config.devices().profile(&ergosnm), Some("ergo"));
+        assert_eq!(config.devices().profile(&trackball), Some("trackball"));
+        assert_eq!(config.profile_names(), vec!["ergo", "laptop", "trackball"]);
+        assert!(config.for_device(&ergosnm).is_none());
+        let laptop_config = config.for_device(&laptop).unwrap();
+        assert_eq!(laptop_config.default_keymap_index, 0);
+        assert_eq!(laptop_config.keymaps, config.keymaps);
+        assert!(laptop_config.profiles.is_empty());
+        assert_eq!(config.for_profile(None).unwrap().default_keymap_index, 1);
+        let trackball_config = config.for_device(&trackball).unwrap();
+        let keymaps =
+            KeyMaps::<TestKeyboard, u16, TestEvent>::new(&test_key_map(), trackball_config)
+                .unwrap();
+        assert_eq!(keymaps.scroll_button(), Some(S));
+        let (keymaps, profiles) =
+            KeyMaps::<TestKeyboard, u16, TestEvent>::with_profiles(&test_key_map(), &config)
+                .unwrap();
+        assert_eq!(keymaps.scroll_button(), None);
+        let mut names: Vec<&str> = profiles.keys().map(|name| name.as_str()).collect();
+        names.sort_unstable();
+        assert_eq!(names, vec!["laptop", "trackball"]);
+        assert_eq!(profiles["trackball"].scroll_button(), Some(S));
+
+        assert_eq!(
+            config_error(
+                r###"
+            switch_layout_keys = []
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = ['ESC, A, S', 'ESC, S, A']
+
+            [profiles.broken]
+            keymaps = ['ESC, A, S', 'ESC, S, NOPE']
+
+            [[devices.grab]]
+            profile = 'missing'
+            "###
+            ),
+            "invalid config: devices.grab[0].profile: unknown profile: missing\ninvalid config: profiles.broken: keymap 1 line 1 column 9: unknown key: NOPE"
+        );
+        assert_eq!(
+            config_error(
+                r###"
+            switch_layout_keys = []
+            revert_keymap_index = 0
+            default_keymap_index = 1
+            keymaps = ['ESC, A, S', 'ESC, S, A']
+            scroll_button = 'NOPE'
+
+            [[devices.always_grab]]
+            profile = 'missing'
+            "###
+            ),
+            "invalid config: scroll_button: unknown key: NOPE\ninvalid config: devices.always_grab[0].profile: unknown profile: missing"
+        );
     }
 }
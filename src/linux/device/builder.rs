@@ -1,4 +1,4 @@
-use crate::{Device, Result};
+use crate::{linux::device::Capabilities, Device, Result};
 use libc::c_int;
 use nix::{self, fcntl, ioctl_none, ioctl_write_ptr, sys::stat, unistd};
 use std::{collections::hash_map::Values, ffi::CString, mem, os::raw::c_char, path::Path, slice};
@@ -7,6 +7,13 @@ use crate::linux::device::codes::*;
 
 ioctl_write_ptr!(ui_set_evbit, b'U', 100, c_int);
 ioctl_write_ptr!(ui_set_keybit, b'U', 101, c_int);
+ioctl_write_ptr!(ui_set_relbit, b'U', 102, c_int);
+ioctl_write_ptr!(ui_set_absbit, b'U', 103, c_int);
+ioctl_write_ptr!(ui_set_mscbit, b'U', 104, c_int);
+ioctl_write_ptr!(ui_set_ledbit, b'U', 105, c_int);
+ioctl_write_ptr!(ui_set_sndbit, b'U', 106, c_int);
+ioctl_write_ptr!(ui_set_swbit, b'U', 109, c_int);
+ioctl_write_ptr!(ui_set_propbit, b'U', 110, c_int);
 ioctl_none!(ui_dev_create, b'U', 1);
 
 pub const UINPUT_MAX_NAME_SIZE: c_int = 80;
@@ -47,7 +54,7 @@ impl Builder {
         Ok(Builder {
             fd: fcntl::open(
                 path.as_ref(),
-                fcntl::OFlag::O_WRONLY | fcntl::OFlag::O_NONBLOCK,
+                fcntl::OFlag::O_RDWR | fcntl::OFlag::O_NONBLOCK,
                 stat::Mode::empty(),
             )?,
             def: unsafe { mem::zeroed() },
@@ -56,6 +63,7 @@ impl Builder {
     }
 
     /// Create a builder from `/dev/uinput`.
+    #[allow(clippy::should_implement_trait)]
     pub fn default() -> Result<Self> {
         Builder::open("/dev/uinput")
     }
@@ -69,7 +77,8 @@ impl Builder {
             Err(nix::Error::EINVAL)?;
         }
 
-        (&mut self.def.name)[..bytes.len()].clone_from_slice(unsafe { mem::transmute(bytes) });
+        (&mut self.def.name)[..bytes.len()]
+            .clone_from_slice(unsafe { mem::transmute::<&[u8], &[c_char]>(bytes) });
 
         Ok(self)
     }
@@ -110,6 +119,56 @@ impl Builder {
         Ok(self)
     }
 
+    /// Enable relative events with these codes, for moving the pointer and scrolling.
+    pub fn relative(mut self, rel_codes: &[c_int]) -> Result<Self> {
+        self.abs = None;
+        unsafe {
+            ui_set_evbit(self.fd, EV_REL as *const c_int)?;
+
+            for rel_code in rel_codes {
+                ui_set_relbit(self.fd, *rel_code as *const c_int)?;
+            }
+        }
+        Ok(self)
+    }
+
+    /// Enable everything in capabilities, usually read from a real device so this can send everything it does.
+    pub fn capabilities(mut self, capabilities: &Capabilities) -> Result<Self> {
+        self.abs = None;
+        unsafe {
+            for prop in capabilities.props.iter() {
+                ui_set_propbit(self.fd, *prop as *const c_int)?;
+            }
+
+            for (kind, codes) in capabilities.codes.iter() {
+                let set_bit: unsafe fn(c_int, *const c_int) -> nix::Result<c_int> = match *kind {
+                    EV_KEY => ui_set_keybit,
+                    EV_REL => ui_set_relbit,
+                    EV_ABS => ui_set_absbit,
+                    EV_MSC => ui_set_mscbit,
+                    EV_LED => ui_set_ledbit,
+                    EV_SND => ui_set_sndbit,
+                    EV_SW => ui_set_swbit,
+                    _ => continue,
+                };
+                ui_set_evbit(self.fd, *kind as *const c_int)?;
+
+                for code in codes {
+                    set_bit(self.fd, *code as *const c_int)?;
+                }
+            }
+        }
+
+        for (axis, absinfo) in capabilities.abs.iter() {
+            let axis = *axis as usize;
+            self.def.absmin[axis] = absinfo.minimum;
+            self.def.absmax[axis] = absinfo.maximum;
+            self.def.absfuzz[axis] = absinfo.fuzz;
+            self.def.absflat[axis] = absinfo.flat;
+        }
+        Ok(self)
+    }
+
     /// Set the maximum value for the previously enabled absolute event.
     pub fn max(mut self, value: i32) -> Self {
         self.def.absmax[self.abs.unwrap() as usize] = value;
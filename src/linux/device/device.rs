@@ -1,7 +1,13 @@
-use crate::Result;
-use libc::{c_int, gettimeofday, input_event, timeval};
-use nix::{ioctl_none, unistd};
-use std::{mem, ptr, slice};
+use crate::{Error, Result};
+use libc::{c_int, input_event, timeval};
+use nix::{errno::Errno, ioctl_none, unistd};
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    mem,
+    os::unix::io::{AsRawFd, RawFd},
+    slice,
+};
 
 use crate::linux::device::codes::*;
 
@@ -10,12 +16,20 @@ ioctl_none!(ui_dev_destroy, b'U', 2);
 /// The virtual device.
 pub struct Device {
     fd: c_int,
+    // keys pressed through this device and not released yet
+    pressed: RefCell<HashSet<u16>>,
+    // events written since the last flush, sent in one write when the frame is synchronized
+    buffer: RefCell<Vec<input_event>>,
 }
 
 impl Device {
     /// Wrap a file descriptor in a `Device`.
     pub fn new(fd: c_int) -> Self {
-        Device { fd: fd }
+        Device {
+            fd,
+            pressed: RefCell::new(HashSet::new()),
+            buffer: RefCell::new(Vec::new()),
+        }
     }
 
     #[doc(hidden)]
@@ -27,26 +41,95 @@ impl Device {
             },
             type_: kind as u16,
             code: code as u16,
-            value: value as i32,
+            value,
         };
 
         self.write_event(&mut event)
     }
 
-    #[doc(hidden)]
+    /// Buffer an event, the buffer is written out when a SYN_REPORT is written or on flush.
+    /// uinput stamps each event with the time it gets it, so the time sent here is ignored.
     pub fn write_event(&self, event: &mut input_event) -> Result<()> {
-        unsafe {
-            gettimeofday(&mut event.time, ptr::null_mut());
+        self.buffer.borrow_mut().push(*event);
+
+        if event.type_ == EV_KEY as u16 {
+            match event.value {
+                0 => {
+                    self.pressed.borrow_mut().remove(&event.code);
+                }
+                1 => {
+                    self.pressed.borrow_mut().insert(event.code);
+                }
+                _ => (),
+            }
+        }
 
-            let ptr = event as *const _ as *const u8;
-            let size = mem::size_of_val(event);
+        if event.type_ == EV_SYN as u16 && event.code == SYN_REPORT as u16 {
+            self.flush()?;
+        }
+        Ok(())
+    }
 
-            unistd::write(self.fd, slice::from_raw_parts(ptr, size))?;
+    /// Write every buffered event to the device in a single write.
+    pub fn flush(&self) -> Result<()> {
+        let mut buffer = self.buffer.borrow_mut();
+        if buffer.is_empty() {
+            return Ok(());
+        }
+        let mut bytes = unsafe {
+            slice::from_raw_parts(
+                buffer.as_ptr() as *const u8,
+                mem::size_of_val(buffer.as_slice()),
+            )
+        };
+        // uinput takes whole events, but a short write would still leave the rest to send
+        while !bytes.is_empty() {
+            let written = unistd::write(self.fd, bytes)?;
+            bytes = &bytes[written..];
         }
+        buffer.clear();
+        Ok(())
+    }
 
+    /// Read an event the kernel sent back to the device, like EV_LED when a lock key changes, None if there are none
+    pub fn read_event(&self) -> Result<Option<input_event>> {
+        let mut buf = [0u8; mem::size_of::<input_event>()];
+        match unistd::read(self.fd, &mut buf) {
+            Ok(num_bytes) if num_bytes == buf.len() => {
+                let event: input_event = unsafe { mem::transmute(buf) };
+                Ok(Some(event))
+            }
+            Ok(_) => Err(Error::ShortRead),
+            Err(Errno::EAGAIN) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    #[cfg(feature = "epoll_inotify")]
+    pub fn epoll_add(&self, epoll_fd: RawFd, data: u64) -> Result<()> {
+        // the fd is already non-blocking, closing it in drop takes it back out of epoll
+        let epoll_event = epoll::Event::new(epoll::Events::EPOLLIN | epoll::Events::EPOLLET, data);
+        epoll::ctl(
+            epoll_fd,
+            epoll::ControlOptions::EPOLL_CTL_ADD,
+            self.fd,
+            epoll_event,
+        )?;
         Ok(())
     }
 
+    /// Release every key pressed through this device that hasn't been released yet.
+    pub fn release_all(&self) -> Result<()> {
+        let pressed: Vec<u16> = self.pressed.borrow().iter().copied().collect();
+        if pressed.is_empty() {
+            return Ok(());
+        }
+        for code in pressed {
+            self.release(EV_KEY, code as c_int)?;
+        }
+        self.synchronize()
+    }
+
     /// Synchronize the device.
     pub fn synchronize(&self) -> Result<()> {
         self.write(EV_SYN, SYN_REPORT, 0)
@@ -79,11 +162,21 @@ impl Device {
     }
 }
 
+impl AsRawFd for Device {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
 impl Drop for Device {
     fn drop(&mut self) {
+        // nothing can release keys still held once the device is gone
+        self.flush().ok();
+        self.release_all().ok();
         unsafe {
             // ignore error here so as to not panic in a drop
             ui_dev_destroy(self.fd).ok();
         }
+        unistd::close(self.fd).ok();
     }
 }
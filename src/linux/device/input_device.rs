@@ -1,23 +1,164 @@
-use libc::{c_int, input_event, input_id};
+use libc::{c_int, input_absinfo, input_event, input_id};
 use nix::{ioctl_read, ioctl_read_buf, ioctl_write_ptr};
-use std::{fs::File, io::Read, mem, os::unix::io::AsRawFd};
-
-#[cfg(feature = "epoll_inotify")]
-use std::os::unix::prelude::RawFd;
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{Read, Write},
+    mem,
+    os::unix::io::{AsRawFd, RawFd},
+};
 
 use crate::{
-    linux::{BTN_LEFT, EV_KEY, KEY_A, KEY_D, KEY_MAX, KEY_S, KEY_W, NAME},
+    linux::{device::codes::*, NAME},
     DeviceIds, DeviceMatchers, Error, Result,
 };
 
 ioctl_write_ptr!(eviocgrab, b'E', 0x90, c_int);
 ioctl_read_buf!(eviocgname, b'E', 0x06, u8);
+ioctl_read_buf!(eviocgphys, b'E', 0x07, u8);
+ioctl_read_buf!(eviocguniq, b'E', 0x08, u8);
 ioctl_read_buf!(eviocgbit, b'E', 0x20, u8);
+ioctl_read_buf!(eviocgprop, b'E', 0x09, u8);
+ioctl_read_buf!(eviocgkey, b'E', 0x18, u8);
+ioctl_read_buf!(eviocgled, b'E', 0x19, u8);
 ioctl_read_buf!(eviocgbit_ev_key, b'E', 0x20 + EV_KEY, u8);
+ioctl_read_buf!(eviocgbit_ev_rel, b'E', 0x20 + EV_REL, u8);
+ioctl_read_buf!(eviocgbit_ev_abs, b'E', 0x20 + EV_ABS, u8);
+ioctl_read_buf!(eviocgbit_ev_msc, b'E', 0x20 + EV_MSC, u8);
+ioctl_read_buf!(eviocgbit_ev_sw, b'E', 0x20 + EV_SW, u8);
+ioctl_read_buf!(eviocgbit_ev_led, b'E', 0x20 + EV_LED, u8);
+ioctl_read_buf!(eviocgbit_ev_snd, b'E', 0x20 + EV_SND, u8);
 ioctl_read!(eviocgid, b'E', 0x02, input_id);
 
 const SIZE_OF_INPUT_EVENT: usize = mem::size_of::<input_event>();
 
+/// How many events read_events reads at once with a buffer from new_input_event_buf
+pub const INPUT_EVENT_BATCH: usize = 64;
+
+/// The keyboard detection rules from DeviceMatchers, with key and event names looked up
+pub struct KeyboardDetection {
+    required_keys: Vec<c_int>,
+    forbidden_keys: Vec<c_int>,
+    required_events: Vec<c_int>,
+}
+
+const EVENTS: [(&str, c_int); 12] = [
+    ("EV_SYN", EV_SYN),
+    ("EV_KEY", EV_KEY),
+    ("EV_REL", EV_REL),
+    ("EV_ABS", EV_ABS),
+    ("EV_MSC", EV_MSC),
+    ("EV_SW", EV_SW),
+    ("EV_LED", EV_LED),
+    ("EV_SND", EV_SND),
+    ("EV_REP", EV_REP),
+    ("EV_FF", EV_FF),
+    ("EV_PWR", EV_PWR),
+    ("EV_FF_STATUS", EV_FF_STATUS),
+];
+
+impl KeyboardDetection {
+    pub fn new(key_map: &HashMap<&'static str, u16>, devices: &DeviceMatchers) -> Result<Self> {
+        // a key or BTN_ name from the keymap, or the number of the code, with or without #
+        let key_code = |what: &str, name: &String| {
+            key_map
+                .get(name.as_str())
+                .map(|code| *code as c_int)
+                .or_else(|| name.trim_start_matches('#').parse().ok())
+                .filter(|code| (0..=KEY_MAX).contains(code))
+                .ok_or_else(|| Error::Config(format!("devices.{}: unknown key: {}", what, name)))
+        };
+        Ok(KeyboardDetection {
+            required_keys: devices
+                .required_keys()
+                .iter()
+                .map(|name| key_code("required_keys", name))
+                .collect::<Result<_>>()?,
+            forbidden_keys: devices
+                .forbidden_keys()
+                .iter()
+                .map(|name| key_code("forbidden_keys", name))
+                .collect::<Result<_>>()?,
+            required_events: devices
+                .required_events()
+                .iter()
+                .map(|name| {
+                    EVENTS
+                        .iter()
+                        .find(|e| e.0 == name)
+                        .map(|e| e.1)
+                        .ok_or_else(|| {
+                            Error::Config(format!(
+                                "devices.required_events: unknown event type: {}",
+                                name
+                            ))
+                        })
+                })
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    fn is_keyboard(&self, raw_fd: c_int) -> Result<bool> {
+        // does it support all required_events
+        let mut evbit = [0u8; 8];
+        unsafe {
+            eviocgbit(raw_fd, &mut evbit)?;
+        };
+        let evbit = u64::from_ne_bytes(evbit);
+        if self
+            .required_events
+            .iter()
+            .any(|ev| (evbit & (1 << ev)) == 0)
+        {
+            return Ok(false);
+        }
+
+        // does it support all required_keys and *not* any forbidden_keys
+        let mut key_bits = [0u8; (KEY_MAX as usize / 8) + 1];
+        if evbit & (1 << EV_KEY) != 0 {
+            unsafe {
+                eviocgbit_ev_key(raw_fd, &mut key_bits)?;
+            };
+        }
+        let key_supported = |key: &c_int| (key_bits[*key as usize / 8] & (1 << (key % 8))) != 0;
+        Ok(self.required_keys.iter().all(key_supported)
+            && !self.forbidden_keys.iter().any(key_supported))
+    }
+}
+
+type IoctlBuf = unsafe fn(c_int, &mut [u8]) -> nix::Result<c_int>;
+
+// event types a virtual device copies from a real one, with the highest code of each
+// EV_REP is left out so the kernel doesn't repeat keys on top of the repeats passed through, and EV_FF because
+// uploading effects would need handled here
+const MIRRORED_EVENTS: [(c_int, IoctlBuf, c_int); 7] = [
+    (EV_KEY, eviocgbit_ev_key, KEY_MAX),
+    (EV_REL, eviocgbit_ev_rel, REL_MAX),
+    (EV_ABS, eviocgbit_ev_abs, ABS_MAX),
+    (EV_MSC, eviocgbit_ev_msc, MSC_MAX),
+    (EV_SW, eviocgbit_ev_sw, SW_MAX),
+    (EV_LED, eviocgbit_ev_led, LED_MAX),
+    (EV_SND, eviocgbit_ev_snd, SND_MAX),
+];
+
+/// Everything an InputDevice can send, so a virtual device can be made to send the same
+pub struct Capabilities {
+    pub props: Vec<c_int>,
+    /// each event type with every code of that type
+    pub codes: Vec<(c_int, Vec<c_int>)>,
+    /// range of every absolute axis
+    pub abs: Vec<(c_int, input_absinfo)>,
+}
+
+/// Everything DeviceMatchers can match an InputDevice on
+#[derive(Debug, Clone)]
+pub struct InputDeviceIds {
+    pub id: input_id,
+    pub name: Option<String>,
+    pub phys: Option<String>,
+    pub uniq: Option<String>,
+}
+
 pub struct InputDevice {
     device_file: File,
     grabbed: bool,
@@ -27,28 +168,60 @@ pub struct InputDevice {
 
 impl InputDevice {
     pub fn open<P: AsRef<std::path::Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
         Ok(InputDevice {
-            device_file: File::open(path)?,
+            // write access is only needed to set its LEDs, so do without if we can't have it
+            device_file: OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(path)
+                .or_else(|_| File::open(path))?,
             grabbed: false,
             #[cfg(feature = "epoll_inotify")]
             epoll_fd: None,
         })
     }
 
-    pub fn new_input_event_buf() -> [u8; SIZE_OF_INPUT_EVENT] {
-        [0u8; SIZE_OF_INPUT_EVENT]
+    pub fn new_input_event_buf() -> [input_event; INPUT_EVENT_BATCH] {
+        // input_event is plain integers, all zeros is a valid one
+        unsafe { mem::zeroed() }
     }
 
-    pub fn read_event(&mut self, buf: &mut [u8; SIZE_OF_INPUT_EVENT]) -> Result<input_event> {
-        let num_bytes = self.device_file.read(buf)?;
-        if num_bytes != SIZE_OF_INPUT_EVENT {
+    /// Read as many events as are ready and fit in buf with one read, at least one
+    pub fn read_events<'a>(&mut self, buf: &'a mut [input_event]) -> Result<&'a [input_event]> {
+        let bytes = unsafe {
+            std::slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut u8, mem::size_of_val(buf))
+        };
+        // evdev only ever hands out whole events
+        let num_bytes = self.device_file.read(bytes)?;
+        if num_bytes == 0 || num_bytes % SIZE_OF_INPUT_EVENT != 0 {
             return Err(Error::ShortRead);
         }
-        let event: input_event = unsafe { mem::transmute(*buf) };
-        Ok(event)
+        Ok(&buf[..num_bytes / SIZE_OF_INPUT_EVENT])
+    }
+
+    /// Write an event to the device, like EV_LED to turn a light on, fails if it was opened without write access
+    pub fn write_event(&self, event: &input_event) -> Result<()> {
+        let buf: [u8; SIZE_OF_INPUT_EVENT] = unsafe { mem::transmute(*event) };
+        (&self.device_file).write_all(&buf)?;
+        Ok(())
+    }
+
+    pub fn valid_keyboard_device(
+        self,
+        detection: &KeyboardDetection,
+        devices: &DeviceMatchers,
+    ) -> Result<Self> {
+        self.check_keyboard_device(detection, devices)?;
+        Ok(self)
     }
 
-    pub fn valid_keyboard_device(self, devices: &DeviceMatchers) -> Result<Self> {
+    /// Whether devices grabs this, with detection built from the same devices, Err(NotAKeyboard) if not
+    pub fn check_keyboard_device(
+        &self,
+        detection: &KeyboardDetection,
+        devices: &DeviceMatchers,
+    ) -> Result<()> {
         use std::os::unix::fs::FileTypeExt;
 
         // must be a character device
@@ -56,40 +229,50 @@ impl InputDevice {
             return Err(Error::NotAKeyboard);
         }
 
-        let raw_fd = self.device_file.as_raw_fd();
+        let ids = self.ids()?;
 
-        // does it support EV_KEY
-        let mut evbit = [0u8; 8];
-        unsafe {
-            eviocgbit(raw_fd, &mut evbit)?;
-        };
-        let evbit = u64::from_ne_bytes(evbit);
-        if (evbit & (1 << EV_KEY)) == 0 {
+        // is it another running copy of rusty-keys ?
+        if ids.name.as_deref().unwrap_or_default().starts_with(NAME) {
             return Err(Error::NotAKeyboard);
         }
 
-        // does it support all keys WASD and *not* LEFT mouse button ? (yes this is fairly random but probably good enough, could make configuration probably)
-        let mut key_bits = [0u8; (KEY_MAX as usize / 8) + 1];
-        unsafe {
-            eviocgbit_ev_key(raw_fd, &mut key_bits)?;
-        };
-        let key_unsupported = |key: c_int| (key_bits[key as usize / 8] & (1 << (key % 8))) == 0;
-        if key_unsupported(KEY_W)
-            || key_unsupported(KEY_A)
-            || key_unsupported(KEY_S)
-            || key_unsupported(KEY_D)
-            || !key_unsupported(BTN_LEFT)
-        {
+        let always_grab = devices.always_grab(&ids);
+        if !always_grab && !detection.is_keyboard(self.device_file.as_raw_fd())? {
             return Err(Error::NotAKeyboard);
         }
 
-        // is it another running copy of rusty-keys ?
-        let mut name = [0u8; NAME.len()];
-        unsafe { eviocgname(raw_fd, &mut name)? };
-        if NAME.as_bytes() == &name {
-            return Err(Error::NotAKeyboard);
+        let id = ids.id;
+        print!(
+            "name: {:?} phys: {:?} uniq: {:?} vendor: 0x{:x} product: 0x{:x}, bustype: 0x{:x}, version: 0x{:x}: ",
+            ids.name.as_deref().unwrap_or_default(),
+            ids.phys.as_deref().unwrap_or_default(),
+            ids.uniq.as_deref().unwrap_or_default(),
+            id.vendor,
+            id.product,
+            id.bustype,
+            id.version
+        );
+        if always_grab {
+            println!("grabbed, always_grab");
+            Ok(())
+        } else if devices.grab(&ids) {
+            println!("grabbed");
+            Ok(())
+        } else {
+            println!("skipped");
+            Err(Error::NotAKeyboard)
         }
+    }
+
+    /// The device number of the device file, the same for every file opened on the same device
+    pub fn rdev(&self) -> Result<u64> {
+        use std::os::unix::fs::MetadataExt;
+
+        Ok(self.device_file.metadata()?.rdev())
+    }
 
+    pub fn ids(&self) -> Result<InputDeviceIds> {
+        let raw_fd = self.device_file.as_raw_fd();
         let mut id = input_id {
             bustype: 0,
             vendor: 0,
@@ -97,19 +280,64 @@ impl InputDevice {
             version: 0,
         };
         unsafe { eviocgid(raw_fd, &mut id)? };
-        print!(
-            "vendor: 0x{:x} product: 0x{:x}, bustype: 0x{:x}, version: 0x{:x}: ",
-            id.vendor, id.product, id.bustype, id.version
-        );
-        if devices.grab(&id) {
-            println!("skipped");
-            Err(Error::NotAKeyboard)
-        } else {
-            println!("grabbed");
-            Ok(self)
+        Ok(InputDeviceIds {
+            id,
+            name: ioctl_string(raw_fd, eviocgname),
+            phys: ioctl_string(raw_fd, eviocgphys),
+            uniq: ioctl_string(raw_fd, eviocguniq),
+        })
+    }
+
+    pub fn capabilities(&self) -> Result<Capabilities> {
+        let raw_fd = self.device_file.as_raw_fd();
+        let mut evbit = [0u8; 8];
+        unsafe {
+            eviocgbit(raw_fd, &mut evbit)?;
+        };
+        let evbit = u64::from_ne_bytes(evbit);
+        let mut codes = Vec::new();
+        for (kind, ioctl, max) in MIRRORED_EVENTS.iter() {
+            if evbit & (1 << kind) != 0 {
+                codes.push((*kind, ioctl_bits(raw_fd, *ioctl, *max)?));
+            }
         }
+        let mut abs = Vec::new();
+        if let Some((_, axes)) = codes.iter().find(|(kind, _)| *kind == EV_ABS) {
+            for axis in axes {
+                let mut absinfo: input_absinfo = unsafe { mem::zeroed() };
+                // EVIOCGABS(axis), the axis is part of the request so it can't be an ioctl_read! fn
+                let request =
+                    nix::request_code_read!(b'E', 0x40 + axis, mem::size_of::<input_absinfo>());
+                nix::errno::Errno::result(unsafe {
+                    libc::ioctl(raw_fd, request as _, &mut absinfo)
+                })?;
+                abs.push((*axis, absinfo));
+            }
+        }
+        Ok(Capabilities {
+            props: ioctl_bits(raw_fd, eviocgprop, INPUT_PROP_MAX)?,
+            codes,
+            abs,
+        })
+    }
+
+    /// Keys held down right now
+    pub fn keys_down(&self) -> Result<Vec<u16>> {
+        Ok(
+            ioctl_bits(self.device_file.as_raw_fd(), eviocgkey, KEY_MAX)?
+                .into_iter()
+                .map(|code| code as u16)
+                .collect(),
+        )
+    }
+
+    /// LEDs lit right now
+    pub fn leds_on(&self) -> Result<Vec<c_int>> {
+        ioctl_bits(self.device_file.as_raw_fd(), eviocgled, LED_MAX)
     }
 
+    /// Grab the device, best done once keys_down is empty, a key grabbed mid-press would never see its
+    /// release wherever it was pressed, and we would see a release we never saw pressed.
     pub fn grab(mut self) -> Result<Self> {
         unsafe {
             eviocgrab(self.device_file.as_raw_fd(), 1 as *const c_int)?;
@@ -121,7 +349,7 @@ impl InputDevice {
     pub fn release(&mut self) -> Result<()> {
         if self.grabbed {
             unsafe {
-                eviocgrab(self.device_file.as_raw_fd(), 0 as *const c_int)?;
+                eviocgrab(self.device_file.as_raw_fd(), std::ptr::null::<c_int>())?;
             }
             self.grabbed = false;
         }
@@ -132,7 +360,7 @@ impl InputDevice {
     pub fn epoll_add(mut self, epoll_fd: RawFd, data: u64) -> Result<Self> {
         use nix::fcntl::{fcntl, FcntlArg, OFlag};
 
-        if None != self.epoll_fd {
+        if self.epoll_fd.is_some() {
             return Err(Error::EpollAlreadyAdded);
         }
         let raw_fd = self.device_file.as_raw_fd();
@@ -170,6 +398,38 @@ impl InputDevice {
     }
 }
 
+// every bit set by an EVIOCGBIT style ioctl, up to max
+fn ioctl_bits(raw_fd: c_int, ioctl: IoctlBuf, max: c_int) -> Result<Vec<c_int>> {
+    let mut bits = vec![0u8; max as usize / 8 + 1];
+    unsafe {
+        ioctl(raw_fd, &mut bits)?;
+    };
+    Ok((0..=max)
+        .filter(|bit| bits[*bit as usize / 8] & (1 << (bit % 8)) != 0)
+        .collect())
+}
+
+// EVIOCGNAME and friends fill in a NUL terminated string, None if the device doesn't have one
+fn ioctl_string(raw_fd: c_int, ioctl: IoctlBuf) -> Option<String> {
+    let mut buf = [0u8; 256];
+    let len = unsafe { ioctl(raw_fd, &mut buf) }.ok()?;
+    let string = buf[..(len.max(0) as usize).min(buf.len())]
+        .split(|b| *b == 0)
+        .next()
+        .unwrap_or_default();
+    if string.is_empty() {
+        None
+    } else {
+        Some(String::from_utf8_lossy(string).into_owned())
+    }
+}
+
+impl AsRawFd for InputDevice {
+    fn as_raw_fd(&self) -> RawFd {
+        self.device_file.as_raw_fd()
+    }
+}
+
 impl Drop for InputDevice {
     fn drop(&mut self) {
         // ignore any errors here, what could we do anyhow?
@@ -196,3 +456,33 @@ impl DeviceIds for input_id {
         Some(self.version)
     }
 }
+
+impl DeviceIds for InputDeviceIds {
+    fn bustype(&self) -> Option<u16> {
+        self.id.bustype()
+    }
+
+    fn vendor(&self) -> Option<u16> {
+        self.id.vendor()
+    }
+
+    fn product(&self) -> Option<u16> {
+        self.id.product()
+    }
+
+    fn version(&self) -> Option<u16> {
+        self.id.version()
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn phys(&self) -> Option<&str> {
+        self.phys.as_deref()
+    }
+
+    fn uniq(&self) -> Option<&str> {
+        self.uniq.as_deref()
+    }
+}
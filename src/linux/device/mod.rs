@@ -1,11 +1,12 @@
 mod builder;
-pub use self::builder::Builder;
+pub use self::builder::{Builder, UINPUT_MAX_NAME_SIZE};
 
 pub mod codes;
 pub use codes::*;
 
+#[allow(clippy::module_inception)]
 mod device;
 pub use self::device::Device;
 
 mod input_device;
-pub use self::input_device::InputDevice;
+pub use self::input_device::{Capabilities, InputDevice, InputDeviceIds, KeyboardDetection};
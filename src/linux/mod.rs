@@ -3,17 +3,35 @@ use crate::*;
 use crate::linux::device::codes::*;
 
 pub mod device;
-pub use device::{Builder, Device, InputDevice};
+pub use device::{
+    Builder, Device, InputDevice, InputDeviceIds, KeyboardDetection, UINPUT_MAX_NAME_SIZE,
+};
 
-use libc::input_event;
-use std::{collections::HashMap, env, process::exit};
+use libc::{c_int, input_event, timeval};
+use nix::sys::{
+    signal::{SigSet, Signal},
+    signalfd::{SfdFlags, SignalFd},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    env,
+    process::exit,
+    time::{Duration, Instant},
+};
 
 #[cfg(feature = "epoll_inotify")]
 const INPUT_FOLDER: &str = "/dev/input/";
 
-// 1 is down, 0 is up
+// how often a device waiting to be grabbed is checked for its keys being released
+const GRAB_POLL_INTERVAL: Duration = Duration::from_millis(10);
+// grabbed anyway after waiting this long, in case a key is stuck down
+const GRAB_WAIT_TIMEOUT: Duration = Duration::from_secs(2);
+
+// 1 is down, 0 is up, 2 is auto-repeat
 const DOWN: i32 = 1;
 const UP: i32 = 0;
+const REPEAT: i32 = 2;
 
 use getopts::Options;
 
@@ -33,6 +51,18 @@ impl KeyEvent<u16> for input_event {
             _ => KeyState::OTHER,
         }
     }
+
+    fn set_code(&mut self, code: u16) {
+        self.code = code;
+    }
+
+    fn set_value(&mut self, value: KeyState) {
+        self.value = match value {
+            KeyState::UP => UP,
+            KeyState::DOWN => DOWN,
+            KeyState::OTHER => REPEAT,
+        };
+    }
 }
 
 impl Keyboard<u16, input_event> for Device {
@@ -83,19 +113,45 @@ impl Keyboard<u16, input_event> for Device {
     fn block_key(&self) -> Result<()> {
         Ok(()) // we don't actually use/need this here
     }
+
+    fn drives_tick(&self) -> bool {
+        true // every loop waits no longer than timeout()
+    }
+
+    fn send_mouse_move(
+        &self,
+        mouse_move: MouseMove,
+        amount: i32,
+        _event: &mut input_event,
+    ) -> Result<()> {
+        let (code, value) = match mouse_move {
+            MouseMove::Up => (REL_Y, -amount),
+            MouseMove::Down => (REL_Y, amount),
+            MouseMove::Left => (REL_X, -amount),
+            MouseMove::Right => (REL_X, amount),
+            MouseMove::WheelUp => (REL_WHEEL, amount),
+            MouseMove::WheelDown => (REL_WHEEL, -amount),
+            MouseMove::WheelLeft => (REL_HWHEEL, -amount),
+            MouseMove::WheelRight => (REL_HWHEEL, amount),
+        };
+        // the SYN_REPORT from the source device follows, like it does for keys
+        self.send(EV_REL, code, value)
+    }
 }
 
 #[derive(Debug)]
 struct Config {
     device_files: Vec<String>,
     config_file: String,
+    check: bool,
 }
 
 impl Config {
-    fn new(device_files: Vec<String>, config_file: String) -> Self {
+    fn new(device_files: Vec<String>, config_file: String, check: bool) -> Self {
         Config {
-            device_files: device_files,
-            config_file: config_file,
+            device_files,
+            config_file,
+            check,
         }
     }
 }
@@ -107,170 +163,888 @@ pub fn main_res() -> Result<()> {
     let key_map = key_map();
     //println!("key_map: {:?}", key_map);
 
-    let device = Builder::open("/dev/uinput")
-        .or_else(|_| Builder::open("/dev/input/uinput"))
-        .or_else(|_| Builder::default())?
-        .name(NAME)?
-        .event(key_map.values())?
-        .create()?;
+    if config.check {
+        return check(&key_map, &config);
+    }
 
     #[cfg(not(feature = "toml_serde"))]
-    let mut key_map = LinuxKeyMaps::new(&key_map, KeymapConfig::default());
+    let keymap_config = KeymapConfig::default();
     #[cfg(feature = "toml_serde")]
-    let mut key_map = LinuxKeyMaps::from_cfg(&key_map, &config.config_file);
-    //println!("keymaps: {:?}", keymaps);
+    let keymap_config = KeymapConfig::from_cfg(&config.config_file)?;
+    let mut built = check_config(&key_map, &keymap_config)?;
 
     let mut input_event_buf = InputDevice::new_input_event_buf();
 
-    if config.device_files.len() == 1 {
-        // shortcut, don't bother with epoll
-        let mut input_device = InputDevice::open(&config.device_files[0])?.grab()?;
+    #[cfg(not(feature = "epoll_inotify"))]
+    {
+        // without epoll, exactly 1 device is supported, and the config is never reloaded
+        if config.device_files.len() != 1 {
+            panic!("without epoll_inotify feature, only exactly 1 device is supported");
+        }
+        let mut pending_grab = PendingGrab::new(InputDevice::open(&config.device_files[0])?);
+        let input_device = loop {
+            match pending_grab.try_grab(Instant::now())? {
+                Ok(input_device) => break input_device,
+                Err(still_pending) => pending_grab = still_pending,
+            }
+            std::thread::sleep(GRAB_POLL_INTERVAL);
+        };
+        let mut mapped_device =
+            MappedDevice::new(input_device, &key_map, &keymap_config, &mut built)?;
+        let mut signal_fd = exit_signal_fd(&[])?;
 
         loop {
-            let event = input_device.read_event(&mut input_event_buf)?;
-            send_event(&mut key_map, event, &device)?
+            let (input_ready, exiting) =
+                mapped_device.wait(&signal_fd, timeout_ms(mapped_device.timeout()))?;
+            if exiting && exit_signal(&mut signal_fd)?.is_some() {
+                // returning drops mapped_device, releasing its keys and destroying its output
+                return Ok(());
+            }
+            if input_ready {
+                let events = mapped_device
+                    .input_device
+                    .read_events(&mut input_event_buf)?;
+                mapped_device.send_events(events)?
+            }
+            mapped_device.sync_leds()?;
+            mapped_device.tick()?
         }
-    } else {
-        #[cfg(not(feature = "epoll_inotify"))]
-        panic!("without epoll_inotify feature, only exactly 1 device is supported");
+    }
 
-        #[cfg(feature = "epoll_inotify")]
-        {
-            use inotify::{Inotify, WatchMask};
-
-            let epoll_fd = epoll::create(true)?;
-            const INOTIFY_DATA: u64 = u64::max_value();
-
-            let (device_files, mut inotify) = if config.device_files.len() > 0 {
-                // we operate on exactly the devices sent in and never watch for new devices
-                (
-                    config
-                        .device_files
-                        .iter()
-                        .map(|device_file| {
-                            InputDevice::open(&device_file).expect("device_file does not exist!")
-                        })
-                        .collect(),
-                    None,
-                )
-            } else {
-                use std::os::unix::io::AsRawFd;
-                // we want to wait forever starting new threads for any new keyboard devices
-                // there is a slight race condition here, if a keyboard is plugged in between the time we
-                // enumerate the devices and set up the inotify watch, we'll miss it, doing it the other way
-                // can bring duplicates though, todo: think about this...
-                let device_files = get_keyboard_devices(&key_map.devices);
-                let mut inotify = Inotify::init()?;
-                inotify.add_watch(INPUT_FOLDER, WatchMask::CREATE)?;
-                let epoll_event = epoll::Event::new(
-                    epoll::Events::EPOLLIN | epoll::Events::EPOLLET,
-                    INOTIFY_DATA,
-                );
-                epoll::ctl(
-                    epoll_fd,
-                    epoll::ControlOptions::EPOLL_CTL_ADD,
-                    inotify.as_raw_fd(),
-                    epoll_event,
-                )?;
-                (device_files, Some(inotify))
+    #[cfg(feature = "epoll_inotify")]
+    {
+        use inotify::{Inotify, WatchMask};
+        use std::os::unix::io::AsRawFd;
+
+        let epoll_fd = epoll::create(true)?;
+        const INOTIFY_DATA: u64 = u64::MAX;
+        const SIGNAL_DATA: u64 = u64::MAX - 1;
+
+        let mut inotify = Inotify::init()?;
+        epoll::ctl(
+            epoll_fd,
+            epoll::ControlOptions::EPOLL_CTL_ADD,
+            inotify.as_raw_fd(),
+            epoll::Event::new(
+                epoll::Events::EPOLLIN | epoll::Events::EPOLLET,
+                INOTIFY_DATA,
+            ),
+        )?;
+
+        // SIGHUP reloads the config, read it from epoll instead of a handler so it is handled between events
+        let mut signal_fd = exit_signal_fd(&[Signal::SIGHUP])?;
+        epoll::ctl(
+            epoll_fd,
+            epoll::ControlOptions::EPOLL_CTL_ADD,
+            signal_fd.as_raw_fd(),
+            epoll::Event::new(epoll::Events::EPOLLIN, SIGNAL_DATA),
+        )?;
+
+        // editors often replace the file instead of writing it, so watch the directory it is in
+        #[cfg(feature = "toml_serde")]
+        let (config_watch, config_file_name) = {
+            let config_path = std::path::Path::new(&config.config_file);
+            let config_dir = match config_path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => std::path::Path::new("."),
             };
-            let mut input_devices = Vec::with_capacity(device_files.len());
-            for (idx, device_file) in device_files.into_iter().enumerate() {
-                input_devices.push(Some(device_file.grab()?.epoll_add(epoll_fd, idx as u64)?));
-            }
+            (
+                inotify.add_watch(config_dir, WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO)?,
+                config_path.file_name().map(|name| name.to_owned()),
+            )
+        };
 
-            let mut epoll_buf = [epoll::Event::new(epoll::Events::empty(), 0); 4];
-            let mut inotify_buf = [0u8; 256];
-
-            loop {
-                let num_events = epoll::wait(epoll_fd, -1, &mut epoll_buf)?;
-                for event in &epoll_buf[0..num_events] {
-                    let idx = event.data as usize;
-                    if let Some(Some(input_device)) = &mut input_devices.get_mut(idx) {
-                        loop {
-                            match input_device.read_event(&mut input_event_buf) {
-                                Ok(event) => {
-                                    //println!("input event: {:?}", event);
-                                    send_event(&mut key_map, event, &device)?
-                                }
-                                Err(err) => {
-                                    if let Error::Io(ref err) = err {
-                                        if err.kind() == std::io::ErrorKind::WouldBlock {
-                                            // go back to epoll event loop
-                                            break;
-                                        }
+        let (device_files, input_folder_watch) = if !config.device_files.is_empty() {
+            // we operate on exactly the devices sent in and never watch for new devices
+            (
+                config
+                    .device_files
+                    .iter()
+                    .map(|device_file| {
+                        InputDevice::open(device_file).expect("device_file does not exist!")
+                    })
+                    .collect(),
+                None,
+            )
+        } else {
+            // we want to wait forever starting new threads for any new keyboard devices
+            // there is a slight race condition here, if a keyboard is plugged in between the time we
+            // enumerate the devices and set up the inotify watch, we'll miss it, doing it the other way
+            // can bring duplicates though, todo: think about this...
+            let device_files = get_keyboard_devices(&built.detection, keymap_config.devices());
+            (
+                device_files,
+                Some(inotify.add_watch(INPUT_FOLDER, WatchMask::CREATE)?),
+            )
+        };
+        let mut input_devices = Vec::with_capacity(device_files.len());
+        // grabbed and added to input_devices once nothing is held on them, checked every time around the loop
+        let mut pending_grabs: Vec<PendingGrab> =
+            device_files.into_iter().map(PendingGrab::new).collect();
+
+        // replaced when the config is reloaded
+        #[cfg(feature = "toml_serde")]
+        let mut keymap_config = keymap_config;
+
+        let mut epoll_buf = [epoll::Event::new(epoll::Events::empty(), 0); 4];
+        let mut inotify_buf = [0u8; 4096];
+
+        loop {
+            let now = Instant::now();
+            for pending_grab in std::mem::take(&mut pending_grabs) {
+                match pending_grab.try_grab(now) {
+                    Ok(Ok(input_device)) => add_device(
+                        epoll_fd,
+                        &mut input_devices,
+                        input_device,
+                        &key_map,
+                        &keymap_config,
+                        &mut built,
+                    )?,
+                    Ok(Err(pending_grab)) => pending_grabs.push(pending_grab),
+                    Err(err) => println!("device went away before it was grabbed: {}", err),
+                }
+            }
+            let timeout = input_devices
+                .iter()
+                .flatten()
+                .filter_map(|mapped_device| mapped_device.timeout())
+                .chain(Some(GRAB_POLL_INTERVAL).filter(|_| !pending_grabs.is_empty()))
+                .min();
+            let num_events = epoll::wait(epoll_fd, timeout_ms(timeout), &mut epoll_buf)?;
+            for mapped_device in input_devices.iter_mut().flatten() {
+                mapped_device.tick()?;
+            }
+            let mut reload = false;
+            for event in &epoll_buf[0..num_events] {
+                let idx = event.data as usize;
+                let input_device = if event.data < OUTPUT_DATA {
+                    input_devices.get_mut(idx)
+                } else {
+                    None
+                };
+                if let Some(Some(mapped_device)) = input_device {
+                    loop {
+                        match mapped_device.input_device.read_events(&mut input_event_buf) {
+                            Ok(events) => {
+                                //println!("input events: {:?}", events);
+                                mapped_device.send_events(events)?
+                            }
+                            Err(err) => {
+                                if let Error::Io(ref err) = err {
+                                    if err.kind() == std::io::ErrorKind::WouldBlock {
+                                        // go back to epoll event loop
+                                        break;
                                     }
-                                    // otherwise it's some other error, don't read anything from this again
-                                    println!("input err: {:?}", err);
-                                    // remove it from input_devices and drop it
-                                    let _ = std::mem::replace(&mut input_devices[idx], None);
-                                    if inotify.is_none() {
-                                        // if we aren't watching with inotify, and the last device is removed (Vec only has None's in it), exit the program
-                                        if input_devices.iter().all(|id| id.is_none()) {
-                                            println!("last device went away, exiting...");
-                                            return Ok(());
-                                        }
+                                }
+                                // otherwise it's some other error, don't read anything from this again
+                                println!("input err: {:?}", err);
+                                // remove it from input_devices and drop it
+                                input_devices[idx].take();
+                                if input_folder_watch.is_none() {
+                                    // if we aren't watching with inotify, and the last device is removed (Vec only has None's in it), exit the program
+                                    if input_devices.iter().all(|id| id.is_none())
+                                        && pending_grabs.is_empty()
+                                    {
+                                        println!("last device went away, exiting...");
+                                        return Ok(());
                                     }
-                                    break;
                                 }
+                                break;
                             }
                         }
-                    } else if event.data == INOTIFY_DATA {
-                        if let Some(inotify) = &mut inotify {
-                            for event in inotify.read_events(&mut inotify_buf)? {
-                                if let Some(device_file) = event.name.and_then(|name| name.to_str())
-                                {
-                                    // check if this is an eligible keyboard device
-                                    let mut path = std::path::PathBuf::new();
-                                    path.push(INPUT_FOLDER);
-                                    path.push(device_file);
-
-                                    if let Ok(input_device) = InputDevice::open(path)
-                                        .and_then(|id| id.valid_keyboard_device(&key_map.devices))
-                                    {
-                                        println!(
-                                            "starting mapping for new keyboard: {}",
-                                            device_file
-                                        );
-
-                                        let idx = input_devices
-                                            .iter()
-                                            .position(|id| id.is_none())
-                                            .unwrap_or(input_devices.len());
-
-                                        let input_device =
-                                            input_device.grab()?.epoll_add(epoll_fd, idx as u64)?;
-
-                                        if idx == input_devices.len() {
-                                            input_devices.push(Some(input_device));
-                                        } else {
-                                            // simply replacing None here
-                                            let _ = std::mem::replace(
-                                                &mut input_devices[idx],
-                                                Some(input_device),
-                                            );
-                                        }
-                                    }
+                    }
+                } else if let Some(Some(mapped_device)) = event
+                    .data
+                    .checked_sub(OUTPUT_DATA)
+                    .filter(|idx| *idx < OUTPUT_DATA)
+                    .and_then(|idx| input_devices.get_mut(idx as usize))
+                {
+                    mapped_device.sync_leds()?;
+                } else if event.data == SIGNAL_DATA {
+                    while let Some(signal) = exit_signal(&mut signal_fd)? {
+                        if signal != Signal::SIGHUP {
+                            // returning drops every mapped device, releasing its keys and destroying its output
+                            return Ok(());
+                        }
+                        reload = true;
+                    }
+                } else if event.data == INOTIFY_DATA {
+                    // edge triggered, so read until there is nothing left
+                    loop {
+                        let events: Vec<_> = inotify
+                            .read_events(&mut inotify_buf)?
+                            .map(|event| (event.wd, event.name.map(|name| name.to_owned())))
+                            .collect();
+                        if events.is_empty() {
+                            break;
+                        }
+                        for (wd, name) in events {
+                            #[cfg(feature = "toml_serde")]
+                            if wd == config_watch && name == config_file_name {
+                                reload = true;
+                                continue;
+                            }
+                            if Some(&wd) != input_folder_watch.as_ref() {
+                                continue;
+                            }
+                            if let Some(device_file) = name.as_ref().and_then(|name| name.to_str())
+                            {
+                                // check if this is an eligible keyboard device
+                                let mut path = std::path::PathBuf::new();
+                                path.push(INPUT_FOLDER);
+                                path.push(device_file);
+
+                                if let Ok(input_device) = InputDevice::open(path).and_then(|id| {
+                                    id.valid_keyboard_device(
+                                        &built.detection,
+                                        keymap_config.devices(),
+                                    )
+                                }) {
+                                    println!("starting mapping for new keyboard: {}", device_file);
+                                    pending_grabs.push(PendingGrab::new(input_device));
                                 }
                             }
                         }
                     }
                 }
             }
+            if reload {
+                #[cfg(feature = "toml_serde")]
+                reload_config(
+                    &mut keymap_config,
+                    &mut built,
+                    &mut input_devices,
+                    // devices given on the command line are used whatever the rules say
+                    input_folder_watch.as_ref().map(|_| &mut pending_grabs),
+                    &key_map,
+                    &config.config_file,
+                );
+                #[cfg(not(feature = "toml_serde"))]
+                println!("no config file to reload without toml_serde feature");
+            }
         }
     }
 }
 
-fn send_event(key_map: &mut LinuxKeyMaps, mut event: input_event, device: &Device) -> Result<()> {
-    if event.type_ == EV_KEY_U16 {
-        // println!("type: {} code: {:?} value: {:?}", event.type_, event.code(), event.value());
-        key_map.send_event(&mut event, &device)?
+// a device's output is watched for LED changes with this plus the device's index, inputs use just the index,
+// both are compared as u64 before becoming a usize index, which may only be 32 bits
+#[cfg(feature = "epoll_inotify")]
+const OUTPUT_DATA: u64 = 1 << 31;
+
+// a device found but not grabbed yet, a key grabbed mid-press would never see its release wherever it was pressed,
+// and we would see a release we never saw pressed
+struct PendingGrab {
+    input_device: InputDevice,
+    since: Instant,
+    // said we are waiting for keys to be released
+    waiting: bool,
+}
+
+impl PendingGrab {
+    fn new(input_device: InputDevice) -> Self {
+        PendingGrab {
+            input_device,
+            since: Instant::now(),
+            waiting: false,
+        }
+    }
+
+    // the grabbed device once no keys are held on it, or it has waited GRAB_WAIT_TIMEOUT, otherwise still pending
+    fn try_grab(mut self, now: Instant) -> Result<std::result::Result<InputDevice, Self>> {
+        let waited = now.saturating_duration_since(self.since);
+        if self.input_device.keys_down()?.is_empty() {
+            return self.input_device.grab().map(Ok);
+        }
+        if waited >= GRAB_WAIT_TIMEOUT {
+            println!(
+                "keys still held after waiting {:?}, grabbing anyway",
+                waited
+            );
+            return self.input_device.grab().map(Ok);
+        }
+        if !self.waiting {
+            println!("waiting for keys to be released before grabbing");
+            self.waiting = true;
+        }
+        Ok(Err(self))
+    }
+}
+
+// starts mapping a grabbed device, in the first free slot of input_devices
+#[cfg(feature = "epoll_inotify")]
+fn add_device(
+    epoll_fd: c_int,
+    input_devices: &mut Vec<Option<MappedDevice>>,
+    input_device: InputDevice,
+    key_map: &HashMap<&'static str, u16>,
+    keymap_config: &KeymapConfig,
+    built: &mut BuiltKeyMaps,
+) -> Result<()> {
+    let idx = input_devices
+        .iter()
+        .position(|id| id.is_none())
+        .unwrap_or(input_devices.len());
+    let input_device = input_device.epoll_add(epoll_fd, idx as u64)?;
+    let mapped_device = MappedDevice::new(input_device, key_map, keymap_config, built)?;
+    mapped_device
+        .output
+        .epoll_add(epoll_fd, OUTPUT_DATA + idx as u64)?;
+    if idx == input_devices.len() {
+        input_devices.push(Some(mapped_device));
     } else {
-        device.write_event(&mut event)?
+        // simply replacing None here
+        input_devices[idx].replace(mapped_device);
     }
     Ok(())
 }
 
+// swap in KeyMaps freshly parsed from config_file, keeping the old ones if anything goes wrong, and if the device rules
+// changed, let go of devices they no longer grab and look for ones they now do, if pending_grabs is given to add them to
+#[cfg(all(feature = "epoll_inotify", feature = "toml_serde"))]
+fn reload_config(
+    keymap_config: &mut KeymapConfig,
+    built: &mut BuiltKeyMaps,
+    input_devices: &mut [Option<MappedDevice>],
+    pending_grabs: Option<&mut Vec<PendingGrab>>,
+    key_map: &HashMap<&'static str, u16>,
+    config_file: &str,
+) {
+    // build everything before swapping anything in, so an error leaves it all as it was
+    let reloaded = KeymapConfig::from_cfg(config_file).and_then(|new_config| {
+        let mut new_built = check_config(key_map, &new_config)?;
+        let key_maps = input_devices
+            .iter()
+            .map(|mapped_device| match mapped_device {
+                // not seeded with the keys held, the output has them all released below, so their releases fall through
+                Some(mapped_device) => mapped_device
+                    .seeded(
+                        device_key_maps(key_map, &new_config, &mapped_device.ids, &mut new_built)?,
+                        false,
+                    )
+                    .map(Some),
+                None => Ok(None),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok((new_config, new_built, key_maps))
+    });
+    let (new_config, new_built, key_maps) = match reloaded {
+        Ok(reloaded) => reloaded,
+        Err(e) => {
+            println!(
+                "error reloading config: {}, keeping old config:\n{}",
+                config_file, e
+            );
+            return;
+        }
+    };
+    let pending_grabs = pending_grabs.filter(|_| new_config.devices() != keymap_config.devices());
+    for (mapped_device, key_maps) in input_devices.iter_mut().zip(key_maps) {
+        let key_maps = match key_maps {
+            Some(key_maps) => key_maps,
+            None => continue,
+        };
+        if pending_grabs.is_some()
+            && mapped_device.as_ref().map_or(false, |mapped_device| {
+                mapped_device
+                    .input_device
+                    .check_keyboard_device(&new_built.detection, new_config.devices())
+                    .is_err()
+            })
+        {
+            // dropping it releases its keys and lets go of the device
+            if let Some(mapped_device) = mapped_device.take() {
+                println!(
+                    "no longer grabbing {}",
+                    mapped_device.ids.name.as_deref().unwrap_or_default()
+                );
+            }
+            continue;
+        }
+        if let Some(mapped_device) = mapped_device {
+            // nothing pressed under the old config can be released by the new one
+            if let Err(e) = mapped_device.output.release_all() {
+                println!("error releasing held keys: {}", e);
+            }
+            mapped_device.use_key_maps(key_maps);
+        }
+    }
+    if let Some(pending_grabs) = pending_grabs {
+        pending_grabs.retain(|pending| {
+            pending
+                .input_device
+                .check_keyboard_device(&new_built.detection, new_config.devices())
+                .is_ok()
+        });
+        let grabbed: Vec<u64> = input_devices
+            .iter()
+            .flatten()
+            .map(|mapped_device| &mapped_device.input_device)
+            .chain(pending_grabs.iter().map(|pending| &pending.input_device))
+            .filter_map(|input_device| input_device.rdev().ok())
+            .collect();
+        for input_device in get_keyboard_devices(&new_built.detection, new_config.devices()) {
+            if input_device
+                .rdev()
+                .map_or(false, |rdev| !grabbed.contains(&rdev))
+            {
+                pending_grabs.push(PendingGrab::new(input_device));
+            }
+        }
+    }
+    *keymap_config = new_config;
+    *built = new_built;
+    println!("reloaded config: {}", config_file);
+}
+
+// a virtual device that can send everything input_device can, so events passed through are never dropped, plus every
+// key and button a keymap can map to and movement for the mouse targets
+fn create_device(
+    key_map: &HashMap<&'static str, u16>,
+    input_device: &InputDevice,
+    ids: &InputDeviceIds,
+) -> Result<Device> {
+    let name = match &ids.name {
+        Some(name) => format!("{} {}", NAME, name),
+        None => NAME.to_string(),
+    };
+    Builder::open("/dev/uinput")
+        .or_else(|_| Builder::open("/dev/input/uinput"))
+        .or_else(|_| Builder::default())?
+        .name(truncate_name(&name))?
+        .bus(ids.id.bustype)
+        .vendor(ids.id.vendor)
+        .product(ids.id.product)
+        .version(ids.id.version)
+        .capabilities(&input_device.capabilities()?)?
+        .event(key_map.values())?
+        .relative(&[REL_X, REL_Y, REL_WHEEL, REL_HWHEEL])?
+        .create()
+}
+
+// uinput names are at most UINPUT_MAX_NAME_SIZE bytes including the nul
+fn truncate_name(name: &str) -> &str {
+    let mut len = name.len().min(UINPUT_MAX_NAME_SIZE as usize - 1);
+    while !name.is_char_boundary(len) {
+        len -= 1;
+    }
+    &name[..len]
+}
+
+// pointer movement this far scrolls the wheel 1 click
+const SCROLL_DISTANCE: i32 = 15;
+
+// while button is held pointer movement scrolls instead, if it never scrolled releasing it clicks
+struct ScrollEmulation {
+    button: u16,
+    held: bool,
+    scrolled: bool,
+    // movement not scrolled yet
+    x: i32,
+    y: i32,
+}
+
+impl ScrollEmulation {
+    fn new(button: u16) -> Self {
+        ScrollEmulation {
+            button,
+            held: false,
+            scrolled: false,
+            x: 0,
+            y: 0,
+        }
+    }
+
+    // true if the event was used up here
+    fn send_event(&mut self, event: &input_event, device: &Device) -> Result<bool> {
+        if event.type_ == EV_KEY_U16 && event.code == self.button {
+            match event.value {
+                DOWN => {
+                    self.held = true;
+                    self.scrolled = false;
+                    self.x = 0;
+                    self.y = 0;
+                }
+                UP => {
+                    self.held = false;
+                    if !self.scrolled {
+                        device.press(EV_KEY, self.button as c_int)?;
+                        device.synchronize()?;
+                        device.release(EV_KEY, self.button as c_int)?;
+                    }
+                }
+                _ => (),
+            }
+            return Ok(true);
+        }
+        if !self.held || event.type_ != EV_REL as u16 {
+            return Ok(false);
+        }
+        // REL_WHEEL is positive up, so moving down scrolls down
+        let (distance, wheel, direction) = match event.code as c_int {
+            REL_X => (&mut self.x, REL_HWHEEL, 1),
+            REL_Y => (&mut self.y, REL_WHEEL, -1),
+            _ => return Ok(false),
+        };
+        *distance += event.value;
+        let clicks = *distance / SCROLL_DISTANCE;
+        if clicks != 0 {
+            *distance -= clicks * SCROLL_DISTANCE;
+            self.scrolled = true;
+            device.send(EV_REL, wheel, clicks * direction)?;
+        }
+        Ok(true)
+    }
+}
+
+// a grabbed device and the KeyMaps used only for it, so each has its own layers, held keys etc
+struct MappedDevice {
+    input_device: InputDevice,
+    // to find its profile again when the config is reloaded
+    #[cfg(all(feature = "epoll_inotify", feature = "toml_serde"))]
+    ids: InputDeviceIds,
+    // the virtual device everything from input_device is written to
+    output: Device,
+    // None passes everything through unchanged
+    key_maps: Option<LinuxKeyMaps>,
+    scroll: Option<ScrollEmulation>,
+    // key codes held on input_device, as far as the events we have read say
+    held: HashSet<u16>,
+    // the kernel dropped events, skip the rest until its SYN_REPORT then resync
+    dropped: bool,
+}
+
+impl MappedDevice {
+    fn new(
+        input_device: InputDevice,
+        key_map: &HashMap<&'static str, u16>,
+        keymap_config: &KeymapConfig,
+        built: &mut BuiltKeyMaps,
+    ) -> Result<Self> {
+        let ids = input_device.ids()?;
+        let key_maps = device_key_maps(key_map, keymap_config, &ids, built)?;
+        let mut mapped_device = MappedDevice {
+            output: create_device(key_map, &input_device, &ids)?,
+            input_device,
+            #[cfg(all(feature = "epoll_inotify", feature = "toml_serde"))]
+            ids,
+            key_maps: None,
+            scroll: None,
+            held: HashSet::new(),
+            dropped: false,
+        };
+        mapped_device.held = mapped_device
+            .input_device
+            .keys_down()?
+            .into_iter()
+            .collect();
+        mapped_device.set_key_maps(key_maps)?;
+        Ok(mapped_device)
+    }
+
+    fn set_key_maps(&mut self, key_maps: Option<LinuxKeyMaps>) -> Result<()> {
+        let key_maps = self.seeded(key_maps, true)?;
+        self.use_key_maps(key_maps);
+        Ok(())
+    }
+
+    // new KeyMaps start from the device's caps lock LED, and if held is true what it really has held down
+    fn seeded(
+        &self,
+        mut key_maps: Option<LinuxKeyMaps>,
+        held: bool,
+    ) -> Result<Option<LinuxKeyMaps>> {
+        if let Some(key_maps) = &mut key_maps {
+            let caps_lock = self.input_device.leds_on()?.contains(&LED_CAPSL);
+            let keys_down = if held {
+                self.input_device.keys_down()?
+            } else {
+                Vec::new()
+            };
+            key_maps.seed_key_state(&keys_down, caps_lock, &self.output);
+        }
+        Ok(key_maps)
+    }
+
+    // key_maps already seeded
+    fn use_key_maps(&mut self, key_maps: Option<LinuxKeyMaps>) {
+        self.scroll = key_maps
+            .as_ref()
+            .and_then(|key_maps| key_maps.scroll_button())
+            .map(ScrollEmulation::new);
+        self.key_maps = key_maps;
+    }
+
+    // events read together, written to the output together
+    fn send_events(&mut self, events: &[input_event]) -> Result<()> {
+        for event in events {
+            self.send_event(*event)?;
+        }
+        self.output.flush()
+    }
+
+    fn send_event(&mut self, event: input_event) -> Result<()> {
+        if event.type_ == EV_SYN as u16 {
+            if event.code == SYN_DROPPED as u16 {
+                self.dropped = true;
+                return Ok(());
+            }
+            if self.dropped && event.code == SYN_REPORT as u16 {
+                self.dropped = false;
+                return self.resync();
+            }
+        }
+        if self.dropped {
+            return Ok(());
+        }
+        if event.type_ == EV_KEY_U16 {
+            match event.value {
+                DOWN => self.held.insert(event.code),
+                UP => self.held.remove(&event.code),
+                _ => false,
+            };
+        }
+        self.map_event(event)
+    }
+
+    // after dropped events, release and press whatever changed meanwhile as if we had read it
+    fn resync(&mut self) -> Result<()> {
+        let down: HashSet<u16> = self.input_device.keys_down()?.into_iter().collect();
+        let mut changes: Vec<(u16, i32)> = self
+            .held
+            .difference(&down)
+            .map(|code| (*code, UP))
+            .collect();
+        changes.extend(down.difference(&self.held).map(|code| (*code, DOWN)));
+        self.held = down;
+        for (code, value) in changes {
+            let mut event = new_key_event();
+            event.code = code;
+            event.value = value;
+            self.map_event(event)?;
+        }
+        self.output.synchronize()
+    }
+
+    fn map_event(&mut self, mut event: input_event) -> Result<()> {
+        let device = &self.output;
+        if let Some(scroll) = &mut self.scroll {
+            if scroll.send_event(&event, device)? {
+                return Ok(());
+            }
+        }
+        match &mut self.key_maps {
+            Some(key_maps) if event.type_ == EV_KEY_U16 => {
+                // println!("type: {} code: {:?} value: {:?}", event.type_, event.code(), event.value());
+                key_maps.send_event(&mut event, device)
+            }
+            _ => device.write_event(&mut event),
+        }
+    }
+
+    // LEDs the desktop set on the output for the lock keys, sent on to the real device
+    fn sync_leds(&mut self) -> Result<()> {
+        while let Some(event) = self.output.read_event()? {
+            if event.type_ != EV_LED as u16 {
+                continue;
+            }
+            if event.code == LED_CAPSL as u16 {
+                if let Some(key_maps) = &mut self.key_maps {
+                    key_maps.set_caps_lock(event.value != 0, &self.output);
+                }
+            }
+            // if it was opened read only the lights just stay as they are
+            self.input_device.write_event(&event).ok();
+        }
+        Ok(())
+    }
+
+    // wait up to timeout milliseconds for an event from the device or an LED change, true if the device has one
+    #[cfg(not(feature = "epoll_inotify"))]
+    fn wait(&self, signal_fd: &SignalFd, timeout: c_int) -> Result<(bool, bool)> {
+        use nix::poll::{poll, PollFd, PollFlags};
+        use std::os::unix::io::AsRawFd;
+
+        let mut fds = [
+            PollFd::new(self.input_device.as_raw_fd(), PollFlags::POLLIN),
+            PollFd::new(signal_fd.as_raw_fd(), PollFlags::POLLIN),
+            PollFd::new(self.output.as_raw_fd(), PollFlags::POLLIN),
+        ];
+        poll(&mut fds, timeout)?;
+        let ready = |fd: &PollFd| {
+            fd.revents()
+                .map_or(false, |revents| revents.contains(PollFlags::POLLIN))
+        };
+        Ok((ready(&fds[0]), ready(&fds[1])))
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.key_maps
+            .as_ref()
+            .and_then(|key_maps| key_maps.timeout())
+    }
+
+    fn tick(&mut self) -> Result<()> {
+        if let Some(key_maps) = &mut self.key_maps {
+            key_maps.tick(&mut new_key_event(), &self.output)?;
+        }
+        self.output.flush()
+    }
+}
+
+// blocks SIGTERM and SIGINT plus any extra signals, so they are read from the returned fd between
+// events instead of killing us before the virtual devices release their keys
+fn exit_signal_fd(extra: &[Signal]) -> Result<SignalFd> {
+    let mut signals = SigSet::empty();
+    for signal in [Signal::SIGTERM, Signal::SIGINT].iter().chain(extra) {
+        signals.add(*signal);
+    }
+    signals.thread_block()?;
+    Ok(SignalFd::with_flags(&signals, SfdFlags::SFD_NONBLOCK)?)
+}
+
+// the next pending signal on signal_fd, printing it if it means we should exit
+fn exit_signal(signal_fd: &mut SignalFd) -> Result<Option<Signal>> {
+    let signal = match signal_fd.read_signal()? {
+        Some(info) => Signal::try_from(info.ssi_signo as c_int)?,
+        None => return Ok(None),
+    };
+    if signal == Signal::SIGTERM || signal == Signal::SIGINT {
+        println!("exiting on {}, releasing held keys", signal);
+    }
+    Ok(Some(signal))
+}
+
+// checks the whole config including every profile, each device gets its own KeyMaps when grabbed
+fn check_config(
+    key_map: &HashMap<&'static str, u16>,
+    keymap_config: &KeymapConfig,
+) -> Result<BuiltKeyMaps> {
+    match (
+        LinuxKeyMaps::with_profiles(key_map, keymap_config),
+        KeyboardDetection::new(key_map, keymap_config.devices()),
+    ) {
+        (Ok((key_maps, profiles)), Ok(detection)) => Ok(BuiltKeyMaps {
+            key_maps: Some(key_maps),
+            profiles,
+            detection,
+        }),
+        (Ok(_), Err(e)) => Err(e),
+        (Err(Error::Errors(mut errors)), Err(e)) => {
+            errors.push(e);
+            Err(Error::Errors(errors))
+        }
+        (Err(keymap_err), Err(e)) => Err(Error::Errors(vec![keymap_err, e])),
+        (Err(e), Ok(_)) => Err(e),
+    }
+}
+
+// KeyMaps check_config built for the config and each profile, each used by the first device it is for, the rest are
+// built again since every device keeps its own state, and the keyboard detection for every device looked at under it
+struct BuiltKeyMaps {
+    key_maps: Option<LinuxKeyMaps>,
+    profiles: HashMap<String, LinuxKeyMaps>,
+    #[cfg_attr(not(feature = "epoll_inotify"), allow(dead_code))]
+    detection: KeyboardDetection,
+}
+
+// KeyMaps for the profile the device with ids is matched to, None for pass-through
+fn device_key_maps(
+    key_map: &HashMap<&'static str, u16>,
+    keymap_config: &KeymapConfig,
+    ids: &InputDeviceIds,
+    built: &mut BuiltKeyMaps,
+) -> Result<Option<LinuxKeyMaps>> {
+    let profile = keymap_config.devices().profile(ids);
+    let device_config = match keymap_config.for_profile(profile) {
+        Some(device_config) => device_config,
+        None => return Ok(None),
+    };
+    let key_maps = match profile {
+        Some(name) => built.profiles.remove(name),
+        None => built.key_maps.take(),
+    };
+    match key_maps {
+        Some(key_maps) => Ok(Some(key_maps)),
+        None => Ok(Some(LinuxKeyMaps::new(key_map, device_config)?)),
+    }
+}
+
+// template for events KeyMaps sends on its own, when there is no input_event to modify
+fn new_key_event() -> input_event {
+    input_event {
+        time: timeval {
+            tv_sec: 0,
+            tv_usec: 0,
+        },
+        type_: EV_KEY_U16,
+        code: 0,
+        value: UP,
+    }
+}
+
+// poll and epoll want milliseconds, -1 for forever, round up so we don't wake before the timeout passes
+fn timeout_ms(timeout: Option<Duration>) -> i32 {
+    match timeout {
+        Some(timeout) => ((timeout.as_micros() + 999) / 1000).min(i32::MAX as u128) as i32,
+        None => -1,
+    }
+}
+
+// parse the config and print what every keymap does, without grabbing anything
+fn check(key_map: &HashMap<&'static str, u16>, config: &Config) -> Result<()> {
+    #[cfg(not(feature = "toml_serde"))]
+    let keymap_config = Ok(KeymapConfig::default());
+    #[cfg(feature = "toml_serde")]
+    let keymap_config = KeymapConfig::from_cfg(&config.config_file);
+    let (keymap_config, built) =
+        match keymap_config.and_then(|c| check_config(key_map, &c).map(|built| (c, built))) {
+            Ok((keymap_config, built)) => {
+                if let Some(key_maps) = &built.key_maps {
+                    print_keymaps(key_map, key_maps, "");
+                }
+                (keymap_config, built)
+            }
+            Err(e) => {
+                println!("{}", e);
+                exit(1);
+            }
+        };
+    for name in keymap_config.profile_names() {
+        match built.profiles.get(name) {
+            Some(key_maps) => print_keymaps(key_map, key_maps, &format!("profile {} ", name)),
+            None => println!("profile {}: pass-through\n", name),
+        }
+    }
+    println!("{} OK", config.config_file);
+    Ok(())
+}
+
+fn print_keymaps(key_map: &HashMap<&'static str, u16>, key_maps: &LinuxKeyMaps, prefix: &str) {
+    for index in 1..key_maps.keymaps_len() {
+        let mappings = key_maps.describe_keymap(key_map, index);
+        if mappings.is_empty() {
+            println!("{}keymap {}: no keys changed\n", prefix, index);
+            continue;
+        }
+        let base_width = mappings.iter().map(|m| m.0.len()).max().unwrap_or(0);
+        let mapped_width = mappings.iter().map(|m| m.1.len()).max().unwrap_or(0);
+        println!("{}keymap {}:", prefix, index);
+        for (base, mapped, flags) in mappings {
+            let line = format!(
+                "  {:base_width$} -> {:mapped_width$}  {}",
+                base,
+                mapped,
+                flags,
+                base_width = base_width,
+                mapped_width = mapped_width
+            );
+            println!("{}", line.trim_end());
+        }
+        println!();
+    }
+}
+
 fn parse_args() -> Config {
     fn print_usage(program: &str, opts: Options) {
         let brief = format!("Usage: {} [options] [device_files...]", program);
@@ -288,6 +1062,11 @@ fn parse_args() -> Config {
         "specify the keymap config file to use (default: /etc/rusty-keys/keymap.toml)",
         "FILE",
     );
+    opts.optflag(
+        "",
+        "check",
+        "check the config file and print what each keymap does, without grabbing any keyboard",
+    );
 
     let matches = opts.parse(&args[1..]);
     if matches.is_err() {
@@ -309,20 +1088,23 @@ fn parse_args() -> Config {
         .opt_str("c")
         .unwrap_or("/etc/rusty-keys/keymap.toml".to_owned());
 
-    Config::new(matches.free, config_file)
+    let check = matches.opt_present("check");
+
+    Config::new(matches.free, config_file, check)
 }
 
 #[cfg(feature = "epoll_inotify")]
-fn get_keyboard_devices(devices: &crate::DeviceMatchers) -> Vec<InputDevice> {
+fn get_keyboard_devices(
+    detection: &KeyboardDetection,
+    devices: &crate::DeviceMatchers,
+) -> Vec<InputDevice> {
     let mut res = Vec::new();
     if let Ok(entries) = std::fs::read_dir(INPUT_FOLDER) {
-        for entry in entries {
-            if let Ok(entry) = entry {
-                if let Ok(input_device) =
-                    InputDevice::open(entry.path()).and_then(|id| id.valid_keyboard_device(devices))
-                {
-                    res.push(input_device);
-                }
+        for entry in entries.flatten() {
+            if let Ok(input_device) = InputDevice::open(entry.path())
+                .and_then(|id| id.valid_keyboard_device(detection, devices))
+            {
+                res.push(input_device);
             }
         }
     }
@@ -330,317 +1112,66 @@ fn get_keyboard_devices(devices: &crate::DeviceMatchers) -> Vec<InputDevice> {
 }
 
 pub fn key_map() -> HashMap<&'static str, u16> {
-    [
-        // generated like:
-        // grep -o 'KEY_[^ :;]*' ~/.cargo/registry/src/github.com-1ecc6299db9ec823/uinput-sys-0.1.3/src/codes | sed 's/^KEY_//' | awk '{print "(\""$1"\", KEY_"$1"),"}'
-        ("RESERVED", KEY_RESERVED),
-        ("ESC", KEY_ESC),
-        ("1", KEY_1),
-        ("2", KEY_2),
-        ("3", KEY_3),
-        ("4", KEY_4),
-        ("5", KEY_5),
-        ("6", KEY_6),
-        ("7", KEY_7),
-        ("8", KEY_8),
-        ("9", KEY_9),
-        ("10", KEY_10),
-        ("MINUS", KEY_MINUS),
-        ("EQUAL", KEY_EQUAL),
-        ("BACKSPACE", KEY_BACKSPACE),
-        ("TAB", KEY_TAB),
-        ("Q", KEY_Q),
-        ("W", KEY_W),
-        ("E", KEY_E),
-        ("R", KEY_R),
-        ("T", KEY_T),
-        ("Y", KEY_Y),
-        ("U", KEY_U),
-        ("I", KEY_I),
-        ("O", KEY_O),
-        ("P", KEY_P),
-        ("LEFTBRACE", KEY_LEFTBRACE),
-        ("RIGHTBRACE", KEY_RIGHTBRACE),
-        ("ENTER", KEY_ENTER),
-        ("LEFTCTRL", KEY_LEFTCTRL),
-        ("A", KEY_A),
-        ("S", KEY_S),
-        ("D", KEY_D),
-        ("F", KEY_F),
-        ("G", KEY_G),
-        ("H", KEY_H),
-        ("J", KEY_J),
-        ("K", KEY_K),
-        ("L", KEY_L),
-        ("SEMICOLON", KEY_SEMICOLON),
-        ("APOSTROPHE", KEY_APOSTROPHE),
-        ("GRAVE", KEY_GRAVE),
-        ("LEFTSHIFT", KEY_LEFTSHIFT),
-        ("BACKSLASH", KEY_BACKSLASH),
-        ("Z", KEY_Z),
-        ("X", KEY_X),
-        ("C", KEY_C),
-        ("V", KEY_V),
-        ("B", KEY_B),
-        ("N", KEY_N),
-        ("M", KEY_M),
-        ("COMMA", KEY_COMMA),
-        ("DOT", KEY_DOT),
-        ("SLASH", KEY_SLASH),
-        ("RIGHTSHIFT", KEY_RIGHTSHIFT),
-        ("KPASTERISK", KEY_KPASTERISK),
-        ("LEFTALT", KEY_LEFTALT),
-        ("SPACE", KEY_SPACE),
-        ("CAPSLOCK", KEY_CAPSLOCK),
-        ("F1", KEY_F1),
-        ("F2", KEY_F2),
-        ("F3", KEY_F3),
-        ("F4", KEY_F4),
-        ("F5", KEY_F5),
-        ("F6", KEY_F6),
-        ("F7", KEY_F7),
-        ("F8", KEY_F8),
-        ("F9", KEY_F9),
-        ("F10", KEY_F10),
-        ("NUMLOCK", KEY_NUMLOCK),
-        ("SCROLLLOCK", KEY_SCROLLLOCK),
-        ("KP7", KEY_KP7),
-        ("KP8", KEY_KP8),
-        ("KP9", KEY_KP9),
-        ("KPMINUS", KEY_KPMINUS),
-        ("KP4", KEY_KP4),
-        ("KP5", KEY_KP5),
-        ("KP6", KEY_KP6),
-        ("KPPLUS", KEY_KPPLUS),
-        ("KP1", KEY_KP1),
-        ("KP2", KEY_KP2),
-        ("KP3", KEY_KP3),
-        ("KP0", KEY_KP0),
-        ("KPDOT", KEY_KPDOT),
-        ("ZENKAKUHANKAKU", KEY_ZENKAKUHANKAKU),
-        ("102ND", KEY_102ND),
-        ("F11", KEY_F11),
-        ("F12", KEY_F12),
-        ("RO", KEY_RO),
-        ("KATAKANA", KEY_KATAKANA),
-        ("HIRAGANA", KEY_HIRAGANA),
-        ("HENKAN", KEY_HENKAN),
-        ("KATAKANAHIRAGANA", KEY_KATAKANAHIRAGANA),
-        ("MUHENKAN", KEY_MUHENKAN),
-        ("KPJPCOMMA", KEY_KPJPCOMMA),
-        ("KPENTER", KEY_KPENTER),
-        ("RIGHTCTRL", KEY_RIGHTCTRL),
-        ("KPSLASH", KEY_KPSLASH),
-        ("SYSRQ", KEY_SYSRQ),
-        ("RIGHTALT", KEY_RIGHTALT),
-        ("LINEFEED", KEY_LINEFEED),
-        ("HOME", KEY_HOME),
-        ("UP", KEY_UP),
-        ("PAGEUP", KEY_PAGEUP),
-        ("LEFT", KEY_LEFT),
-        ("RIGHT", KEY_RIGHT),
-        ("END", KEY_END),
-        ("DOWN", KEY_DOWN),
-        ("PAGEDOWN", KEY_PAGEDOWN),
-        ("INSERT", KEY_INSERT),
-        ("DELETE", KEY_DELETE),
-        ("MACRO", KEY_MACRO),
-        ("MUTE", KEY_MUTE),
-        ("VOLUMEDOWN", KEY_VOLUMEDOWN),
-        ("VOLUMEUP", KEY_VOLUMEUP),
-        ("POWER", KEY_POWER),
-        ("KPEQUAL", KEY_KPEQUAL),
-        ("KPPLUSMINUS", KEY_KPPLUSMINUS),
-        ("PAUSE", KEY_PAUSE),
-        ("SCALE", KEY_SCALE),
-        ("KPCOMMA", KEY_KPCOMMA),
-        ("HANGEUL", KEY_HANGEUL),
-        ("HANGUEL", KEY_HANGUEL),
-        ("HANGEUL", KEY_HANGEUL),
-        ("HANJA", KEY_HANJA),
-        ("YEN", KEY_YEN),
-        ("LEFTMETA", KEY_LEFTMETA),
-        ("RIGHTMETA", KEY_RIGHTMETA),
-        ("COMPOSE", KEY_COMPOSE),
-        ("STOP", KEY_STOP),
-        ("AGAIN", KEY_AGAIN),
-        ("PROPS", KEY_PROPS),
-        ("UNDO", KEY_UNDO),
-        ("FRONT", KEY_FRONT),
-        ("COPY", KEY_COPY),
-        ("OPEN", KEY_OPEN),
-        ("PASTE", KEY_PASTE),
-        ("FIND", KEY_FIND),
-        ("CUT", KEY_CUT),
-        ("HELP", KEY_HELP),
-        ("MENU", KEY_MENU),
-        ("CALC", KEY_CALC),
-        ("SETUP", KEY_SETUP),
-        ("SLEEP", KEY_SLEEP),
-        ("WAKEUP", KEY_WAKEUP),
-        ("FILE", KEY_FILE),
-        ("SENDFILE", KEY_SENDFILE),
-        ("DELETEFILE", KEY_DELETEFILE),
-        ("XFER", KEY_XFER),
-        ("PROG1", KEY_PROG1),
-        ("PROG2", KEY_PROG2),
-        ("WWW", KEY_WWW),
-        ("MSDOS", KEY_MSDOS),
-        ("COFFEE", KEY_COFFEE),
-        ("SCREENLOCK", KEY_SCREENLOCK),
-        ("COFFEE", KEY_COFFEE),
-        ("ROTATE_DISPLAY", KEY_ROTATE_DISPLAY),
-        ("DIRECTION", KEY_DIRECTION),
-        ("ROTATE_DISPLAY", KEY_ROTATE_DISPLAY),
-        ("CYCLEWINDOWS", KEY_CYCLEWINDOWS),
-        ("MAIL", KEY_MAIL),
-        ("BOOKMARKS", KEY_BOOKMARKS),
-        ("COMPUTER", KEY_COMPUTER),
-        ("BACK", KEY_BACK),
-        ("FORWARD", KEY_FORWARD),
-        ("CLOSECD", KEY_CLOSECD),
-        ("EJECTCD", KEY_EJECTCD),
-        ("EJECTCLOSECD", KEY_EJECTCLOSECD),
-        ("NEXTSONG", KEY_NEXTSONG),
-        ("PLAYPAUSE", KEY_PLAYPAUSE),
-        ("PREVIOUSSONG", KEY_PREVIOUSSONG),
-        ("STOPCD", KEY_STOPCD),
-        ("RECORD", KEY_RECORD),
-        ("REWIND", KEY_REWIND),
-        ("PHONE", KEY_PHONE),
-        ("ISO", KEY_ISO),
-        ("CONFIG", KEY_CONFIG),
-        ("HOMEPAGE", KEY_HOMEPAGE),
-        ("REFRESH", KEY_REFRESH),
-        ("EXIT", KEY_EXIT),
-        ("MOVE", KEY_MOVE),
-        ("EDIT", KEY_EDIT),
-        ("SCROLLUP", KEY_SCROLLUP),
-        ("SCROLLDOWN", KEY_SCROLLDOWN),
-        ("KPLEFTPAREN", KEY_KPLEFTPAREN),
-        ("KPRIGHTPAREN", KEY_KPRIGHTPAREN),
-        ("NEW", KEY_NEW),
-        ("REDO", KEY_REDO),
-        ("F13", KEY_F13),
-        ("F14", KEY_F14),
-        ("F15", KEY_F15),
-        ("F16", KEY_F16),
-        ("F17", KEY_F17),
-        ("F18", KEY_F18),
-        ("F19", KEY_F19),
-        ("F20", KEY_F20),
-        ("F21", KEY_F21),
-        ("F22", KEY_F22),
-        ("F23", KEY_F23),
-        ("F24", KEY_F24),
-        ("PLAYCD", KEY_PLAYCD),
-        ("PAUSECD", KEY_PAUSECD),
-        ("PROG3", KEY_PROG3),
-        ("PROG4", KEY_PROG4),
-        ("DASHBOARD", KEY_DASHBOARD),
-        ("SUSPEND", KEY_SUSPEND),
-        ("CLOSE", KEY_CLOSE),
-        ("PLAY", KEY_PLAY),
-        ("FASTFORWARD", KEY_FASTFORWARD),
-        ("BASSBOOST", KEY_BASSBOOST),
-        ("PRINT", KEY_PRINT),
-        ("HP", KEY_HP),
-        ("CAMERA", KEY_CAMERA),
-        ("SOUND", KEY_SOUND),
-        ("QUESTION", KEY_QUESTION),
-        ("EMAIL", KEY_EMAIL),
-        ("CHAT", KEY_CHAT),
-        ("SEARCH", KEY_SEARCH),
-        ("CONNECT", KEY_CONNECT),
-        ("FINANCE", KEY_FINANCE),
-        ("SPORT", KEY_SPORT),
-        ("SHOP", KEY_SHOP),
-        ("ALTERASE", KEY_ALTERASE),
-        ("CANCEL", KEY_CANCEL),
-        ("BRIGHTNESSDOWN", KEY_BRIGHTNESSDOWN),
-        ("BRIGHTNESSUP", KEY_BRIGHTNESSUP),
-        ("MEDIA", KEY_MEDIA),
-        ("SWITCHVIDEOMODE", KEY_SWITCHVIDEOMODE),
-        ("KBDILLUMTOGGLE", KEY_KBDILLUMTOGGLE),
-        ("KBDILLUMDOWN", KEY_KBDILLUMDOWN),
-        ("KBDILLUMUP", KEY_KBDILLUMUP),
-        ("SEND", KEY_SEND),
-        ("REPLY", KEY_REPLY),
-        ("FORWARDMAIL", KEY_FORWARDMAIL),
-        ("SAVE", KEY_SAVE),
-        ("DOCUMENTS", KEY_DOCUMENTS),
-        ("BATTERY", KEY_BATTERY),
-        ("BLUETOOTH", KEY_BLUETOOTH),
-        ("WLAN", KEY_WLAN),
-        ("UWB", KEY_UWB),
-        ("UNKNOWN", KEY_UNKNOWN),
-        ("VIDEO_NEXT", KEY_VIDEO_NEXT),
-        ("VIDEO_PREV", KEY_VIDEO_PREV),
-        ("BRIGHTNESS_CYCLE", KEY_BRIGHTNESS_CYCLE),
-        ("BRIGHTNESS_AUTO", KEY_BRIGHTNESS_AUTO),
-        ("BRIGHTNESS_ZERO", KEY_BRIGHTNESS_ZERO),
-        ("BRIGHTNESS_AUTO", KEY_BRIGHTNESS_AUTO),
-        ("DISPLAY_OFF", KEY_DISPLAY_OFF),
-        ("WWAN", KEY_WWAN),
-        ("WIMAX", KEY_WIMAX),
-        ("WWAN", KEY_WWAN),
-        ("RFKILL", KEY_RFKILL),
-        ("MICMUTE", KEY_MICMUTE),
-        // below manual shortcuts
-        ("PSCR", KEY_SYSRQ),
-        ("SLCK", KEY_SCROLLLOCK),
-        ("BRK", KEY_PAUSE),
-        ("GRV", KEY_GRAVE),
-        ("0", KEY_10), // dumb or named wrong?
-        ("MINS", KEY_MINUS),
-        ("EQL", KEY_EQUAL),
-        ("BSPC", KEY_BACKSPACE),
-        ("LBRC", KEY_LEFTBRACE),
-        ("RBRC", KEY_RIGHTBRACE),
-        ("BSLS", KEY_BACKSLASH),
-        ("SCLN", KEY_SEMICOLON),
-        ("QUOT", KEY_APOSTROPHE),
-        ("ENT", KEY_ENTER),
-        ("COMM", KEY_COMMA),
-        ("DOT", KEY_DOT),
-        ("SLSH", KEY_SLASH),
-        ("CAPS", KEY_CAPSLOCK),
-        ("LSFT", KEY_LEFTSHIFT),
-        ("RSFT", KEY_RIGHTSHIFT),
-        ("SPC", KEY_SPACE),
-        ("APP", KEY_COMPOSE),
-        ("LCTL", KEY_LEFTCTRL),
-        ("RCTL", KEY_RIGHTCTRL),
-        ("LALT", KEY_LEFTALT),
-        ("RALT", KEY_RIGHTALT),
-        ("LGUI", KEY_LEFTMETA),
-        ("RGUI", KEY_RIGHTMETA),
-        ("INS", KEY_INSERT),
-        ("PGUP", KEY_PAGEUP),
-        ("PGDN", KEY_PAGEDOWN),
-        ("DEL", KEY_DELETE),
-        ("RGHT", KEY_RIGHT),
-        ("NLCK", KEY_NUMLOCK),
-        ("PSLS", KEY_KPSLASH),
-        ("PAST", KEY_KPASTERISK),
-        ("PMNS", KEY_KPMINUS),
-        ("P7", KEY_KP7),
-        ("P8", KEY_KP8),
-        ("P9", KEY_KP9),
-        ("P4", KEY_KP4),
-        ("P5", KEY_KP5),
-        ("P6", KEY_KP6),
-        ("PPLS", KEY_KPPLUS),
-        ("P1", KEY_KP1),
-        ("P2", KEY_KP2),
-        ("P3", KEY_KP3),
-        ("P0", KEY_KP0),
-        ("PDOT", KEY_KPDOT),
-        ("PENT", KEY_KPENTER),
-        ("TOUCHPAD_TOGGLE", KEY_TOUCHPAD_TOGGLE),
-    ]
-    .iter()
-    .cloned()
-    .map(|(m, v)| (m, v as u16))
-    .collect()
+    // every KEY_ and BTN_ code from codes.rs, keys without their KEY_ prefix
+    KEY_NAMES
+        .iter()
+        .map(|&(name, code)| (name.strip_prefix("KEY_").unwrap_or(name), code))
+        .chain(
+            [
+                // manual shortcuts
+                ("PSCR", KEY_SYSRQ),
+                ("SLCK", KEY_SCROLLLOCK),
+                ("BRK", KEY_PAUSE),
+                ("GRV", KEY_GRAVE),
+                ("0", KEY_10), // dumb or named wrong?
+                ("MINS", KEY_MINUS),
+                ("EQL", KEY_EQUAL),
+                ("BSPC", KEY_BACKSPACE),
+                ("LBRC", KEY_LEFTBRACE),
+                ("RBRC", KEY_RIGHTBRACE),
+                ("BSLS", KEY_BACKSLASH),
+                ("SCLN", KEY_SEMICOLON),
+                ("QUOT", KEY_APOSTROPHE),
+                ("ENT", KEY_ENTER),
+                ("COMM", KEY_COMMA),
+                ("SLSH", KEY_SLASH),
+                ("CAPS", KEY_CAPSLOCK),
+                ("LSFT", KEY_LEFTSHIFT),
+                ("RSFT", KEY_RIGHTSHIFT),
+                ("SPC", KEY_SPACE),
+                ("APP", KEY_COMPOSE),
+                ("LCTL", KEY_LEFTCTRL),
+                ("RCTL", KEY_RIGHTCTRL),
+                ("LALT", KEY_LEFTALT),
+                ("RALT", KEY_RIGHTALT),
+                ("LGUI", KEY_LEFTMETA),
+                ("RGUI", KEY_RIGHTMETA),
+                ("INS", KEY_INSERT),
+                ("PGUP", KEY_PAGEUP),
+                ("PGDN", KEY_PAGEDOWN),
+                ("DEL", KEY_DELETE),
+                ("RGHT", KEY_RIGHT),
+                ("NLCK", KEY_NUMLOCK),
+                ("PSLS", KEY_KPSLASH),
+                ("PAST", KEY_KPASTERISK),
+                ("PMNS", KEY_KPMINUS),
+                ("P7", KEY_KP7),
+                ("P8", KEY_KP8),
+                ("P9", KEY_KP9),
+                ("P4", KEY_KP4),
+                ("P5", KEY_KP5),
+                ("P6", KEY_KP6),
+                ("PPLS", KEY_KPPLUS),
+                ("P1", KEY_KP1),
+                ("P2", KEY_KP2),
+                ("P3", KEY_KP3),
+                ("P0", KEY_KP0),
+                ("PDOT", KEY_KPDOT),
+                ("PENT", KEY_KPENTER),
+            ]
+            .iter()
+            .cloned(),
+        )
+        .map(|(m, v)| (m, v as u16))
+        .collect()
 }
@@ -20,7 +20,8 @@ use core_graphics::{
 use core_graphics::event::{CGEventTapLocation, CGEventType};
 
 type MacOSKeyMaps = KeyMaps<CGEventSource, CGKeyCode, CGEvent, Option<CGEvent>>;
-type CallbackPointer = (MacOSKeyMaps, CGEventSource);
+// the timer is the one that calls tick
+type CallbackPointer = (MacOSKeyMaps, CGEventSource, CFRunLoopTimerRef);
 
 /*
 // possible types for event_source
@@ -39,6 +40,8 @@ const delay: std::time::Duration = std::time::Duration::from_millis(20);
 const tapLocation: CGEventTapLocation = CGEventTapLocation::Session;
 // this is only used if tapLocation is HID, to prevent us from mapping our own key inputs
 const uniqueHIDUserData: i64 = 45;
+// seconds until the tick timer fires while KeyMaps isn't waiting on anything, about 30 years
+const tickNever: CFTimeInterval = 1.0e9;
 
 impl KeyEvent<CGKeyCode> for CGEvent {
     fn code(&self) -> CGKeyCode {
@@ -82,6 +85,17 @@ impl KeyEvent<CGKeyCode> for CGEvent {
             }
         }
     }
+
+    fn set_code(&mut self, code: CGKeyCode) {
+        self.set_integer_value_field(EventField::KEYBOARD_EVENT_KEYCODE, code as i64);
+    }
+
+    fn set_value(&mut self, value: KeyState) {
+        self.set_type(match value {
+            KeyState::UP => CGEventType::KeyUp,
+            _ => CGEventType::KeyDown,
+        });
+    }
 }
 
 impl Keyboard<CGKeyCode, CGEvent, Option<CGEvent>> for CGEventSource {
@@ -145,6 +159,10 @@ impl Keyboard<CGKeyCode, CGEvent, Option<CGEvent>> for CGEventSource {
     fn block_key(&self) -> Result<Option<CGEvent>> {
         Ok(None)
     }
+
+    fn drives_tick(&self) -> bool {
+        true // from the run loop timer
+    }
 }
 
 pub fn main_res() -> Result<()> {
@@ -154,11 +172,12 @@ pub fn main_res() -> Result<()> {
     let key_map = key_map();
     //println!("key_map: {:?}", key_map);
 
-    let key_maps = MacOSKeyMaps::from_cfg(&key_map, &config.config_file);
+    let key_maps = MacOSKeyMaps::from_cfg(&key_map, &config.config_file)?;
     //println!("key_maps: {}", key_maps);
-    let callback_pointer: CallbackPointer = (
+    let mut callback_pointer: CallbackPointer = (
         key_maps,
         CGEventSource::new(CGEventSourceStateID::Private).expect("Failed creating event source"),
+        std::ptr::null_mut(),
     );
 
     let mask = CGEventMaskBit(CGEventType::KeyDown)
@@ -166,6 +185,30 @@ pub fn main_res() -> Result<()> {
         | CGEventMaskBit(CGEventType::FlagsChanged);
 
     unsafe {
+        let allocator = kCFAllocatorDefault;
+        let current_event_loop = CFRunLoopGetCurrent();
+        let mode = kCFRunLoopCommonModes;
+
+        // repeats so it stays valid, schedule_tick moves when it next fires
+        let mut context = CFRunLoopTimerContext {
+            version: 0,
+            info: &mut callback_pointer as *mut CallbackPointer as Pointer,
+            retain: std::ptr::null_mut(),
+            release: std::ptr::null_mut(),
+            copyDescription: std::ptr::null_mut(),
+        };
+        let tick_timer = CFRunLoopTimerCreate(
+            allocator,
+            CFAbsoluteTimeGetCurrent() + tickNever,
+            tickNever,
+            0,
+            0,
+            tick_callback,
+            &mut context,
+        );
+        CFRunLoopAddTimer(current_event_loop, tick_timer, mode);
+        callback_pointer.2 = tick_timer;
+
         let options = 0;
 
         // Create the event tap
@@ -182,10 +225,6 @@ pub fn main_res() -> Result<()> {
         }
         println!("Created event tap...");
 
-        let allocator = kCFAllocatorDefault;
-        let current_event_loop = CFRunLoopGetCurrent();
-        let mode = kCFRunLoopCommonModes;
-
         // Create Run Loop Source
         let run_loop_source = CFMachPortCreateRunLoopSource(allocator, event_tap, 0);
 
@@ -288,15 +327,31 @@ use libc;
 // Opaque Pointer Types
 type Pointer = *mut libc::c_void;
 type CFMachPortRef = Pointer;
+type CFRunLoopTimerRef = Pointer;
 
 // Integer Types
 type CGEventMask = u64;
 type CGEventTapOptions = u32;
 type CGEventTapPlacement = u32;
+type CFAbsoluteTime = f64;
+type CFTimeInterval = f64;
+type CFOptionFlags = libc::c_ulong;
+type CFIndex = libc::c_long;
 
 // Callback Type
 type CGEventTapCallBack =
     extern "C" fn(Pointer, CGEventType, CGEvent, &mut CallbackPointer) -> CGEvent;
+type CFRunLoopTimerCallBack = extern "C" fn(CFRunLoopTimerRef, &mut CallbackPointer);
+
+// info is passed to the timer's callback, we don't need the rest
+#[repr(C)]
+struct CFRunLoopTimerContext {
+    version: CFIndex,
+    info: Pointer,
+    retain: Pointer,
+    release: Pointer,
+    copyDescription: Pointer,
+}
 
 // Constants
 const kCGSessionEventTap: CGEventTapLocation = CGEventTapLocation::HID;
@@ -385,6 +440,28 @@ extern "C" {
     pub fn CFRunLoopAddSource(run_loop: Pointer, run_loop_source: Pointer, mode: Pointer);
 
     pub fn CGEventTapEnable(port: CFMachPortRef, enable: bool);
+
+    /// The current time, which run loop timers are scheduled in
+    pub fn CFAbsoluteTimeGetCurrent() -> CFAbsoluteTime;
+
+    /// Create a timer that calls callout with context's info
+    /// from the run loop it is added to, every interval seconds
+    /// after fireDate
+    pub fn CFRunLoopTimerCreate(
+        allocator: Pointer,
+        fireDate: CFAbsoluteTime,
+        interval: CFTimeInterval,
+        flags: CFOptionFlags,
+        order: CFIndex,
+        callout: CFRunLoopTimerCallBack,
+        context: *mut CFRunLoopTimerContext,
+    ) -> CFRunLoopTimerRef;
+
+    /// Adds a CFRunLoopTimer object to a run loop mode.
+    pub fn CFRunLoopAddTimer(run_loop: Pointer, timer: CFRunLoopTimerRef, mode: Pointer);
+
+    /// Moves when a timer next fires, its interval still applies after
+    pub fn CFRunLoopTimerSetNextFireDate(timer: CFRunLoopTimerRef, fireDate: CFAbsoluteTime);
 }
 
 const NX_DEVICELCTLKEYMASK: u64 = 0x00000001;
@@ -408,7 +485,7 @@ pub extern "C" fn callback(
     mut event: CGEvent,
     callback_pointer: &mut CallbackPointer,
 ) -> CGEvent {
-    let (key_maps, event_source) = callback_pointer;
+    let (key_maps, event_source, tick_timer) = callback_pointer;
     match tapLocation {
         CGEventTapLocation::HID => {
             let user_data = event.get_integer_value_field(EventField::EVENT_SOURCE_USER_DATA);
@@ -419,13 +496,35 @@ pub extern "C" fn callback(
         _ => {}
     };
 
-    key_maps
+    let ret = key_maps
         .send_event(&mut event, &event_source)
-        .expect("macos shouldn't error...")
-        .unwrap_or_else(|| {
-            event.set_type(CGEventType::Null);
-            event
-        }) // None means return NULL
+        .expect("macos shouldn't error...");
+    schedule_tick(key_maps, *tick_timer);
+    ret.unwrap_or_else(|| {
+        event.set_type(CGEventType::Null);
+        event
+    }) // None means return NULL
+}
+
+///  Called by the tick timer from the same run loop as callback,
+///  when KeyMaps::timeout has passed.
+pub extern "C" fn tick_callback(_timer: CFRunLoopTimerRef, callback_pointer: &mut CallbackPointer) {
+    let (key_maps, event_source, tick_timer) = callback_pointer;
+    // only a template to send keys with
+    let mut event =
+        CGEvent::new_keyboard_event(event_source.clone(), 0, true).expect("Failed creating event");
+    key_maps
+        .tick(&mut event, &event_source)
+        .expect("macos shouldn't error...");
+    schedule_tick(key_maps, *tick_timer);
+}
+
+// KeyMaps only sees time pass when tick is called, so keep the timer set for when it next needs to be
+fn schedule_tick(key_maps: &MacOSKeyMaps, tick_timer: CFRunLoopTimerRef) {
+    let wait = key_maps
+        .timeout()
+        .map_or(tickNever, |timeout| timeout.as_secs_f64());
+    unsafe { CFRunLoopTimerSetNextFireDate(tick_timer, CFAbsoluteTimeGetCurrent() + wait) };
 }
 
 /// Redefine macro for bitshifting from header as function here
@@ -9,14 +9,15 @@ use std::fs::File;
 use winapi::{
     ctypes::c_int,
     shared::{
-        basetsd::ULONG_PTR,
-        minwindef::{HINSTANCE, LPARAM, LRESULT, WPARAM},
+        basetsd::{UINT_PTR, ULONG_PTR},
+        minwindef::{DWORD, HINSTANCE, LPARAM, LRESULT, UINT, WPARAM},
         windef::{HHOOK__, HWND},
     },
     um::winuser::{
-        CallNextHookEx, GetMessageW, MapVirtualKeyW, SendInput, SetWindowsHookExW, ShowWindow,
-        INPUT, INPUT_KEYBOARD, KBDLLHOOKSTRUCT, KEYBDINPUT, KEYEVENTF_KEYUP, KEYEVENTF_SCANCODE,
-        LPINPUT, MAPVK_VK_TO_VSC, MSG, SW_HIDE, WH_KEYBOARD_LL, WM_KEYDOWN, WM_KEYUP,
+        CallNextHookEx, DispatchMessageW, GetMessageW, KillTimer, MapVirtualKeyW, SendInput,
+        SetTimer, SetWindowsHookExW, ShowWindow, INPUT, INPUT_KEYBOARD, KBDLLHOOKSTRUCT,
+        KEYBDINPUT, KEYEVENTF_KEYUP, KEYEVENTF_SCANCODE, LPINPUT, MAPVK_VK_TO_VSC, MSG, SW_HIDE,
+        WH_KEYBOARD_LL, WM_KEYDOWN, WM_KEYUP,
     },
 };
 
@@ -24,7 +25,7 @@ pub mod codes;
 use codes::*;
 use std::{
     mem::{size_of, zeroed},
-    sync::atomic::{AtomicPtr, Ordering},
+    sync::atomic::{AtomicPtr, AtomicUsize, Ordering},
 };
 use winapi::_core::{mem::transmute_copy, ptr::null_mut};
 
@@ -37,6 +38,9 @@ type WindowsKeyMaps = KeyMaps<Device, USizeableDWORD, InputEvent, LRESULT>;
 // this is used for identifying the fake keypresses we insert, so we don't process them in an infinite loop
 const FAKE_EXTRA_INFO: ULONG_PTR = 332;
 
+// id of the timer that calls tick, 0 while none is set
+static TICK_TIMER: AtomicUsize = AtomicUsize::new(0);
+
 // non-zero means don't send on, I think https://msdn.microsoft.com/en-us/library/windows/desktop/ms644984(v=vs.85).aspx
 const BLOCK_KEY: LRESULT = 1;
 
@@ -45,6 +49,8 @@ struct InputEvent {
     value: WPARAM,
     kb_hook_pointer: LPARAM,
     kb_hook_struct: KBDLLHOOKSTRUCT,
+    // true if set_code or set_value changed this, so it can't be passed on to CallNextHookEx as-is
+    modified: bool,
 }
 
 impl KeyEvent<USizeableDWORD> for InputEvent {
@@ -59,12 +65,28 @@ impl KeyEvent<USizeableDWORD> for InputEvent {
             _ => KeyState::OTHER,
         }
     }
+
+    fn set_code(&mut self, code: USizeableDWORD) {
+        self.kb_hook_struct.vkCode = code.0;
+        self.modified = true;
+    }
+
+    fn set_value(&mut self, value: KeyState) {
+        self.value = match value {
+            KeyState::UP => WM_KEYUP,
+            _ => WM_KEYDOWN,
+        } as WPARAM;
+        self.modified = true;
+    }
 }
 
 struct Device;
 
 impl Keyboard<USizeableDWORD, InputEvent, LRESULT> for Device {
     fn send(&self, event: &mut InputEvent) -> Result<LRESULT> {
+        if event.modified {
+            return self.send_mod_code(event.code(), event);
+        }
         Ok(unsafe { CallNextHookEx(null_mut(), event.code, event.value, event.kb_hook_pointer) })
     }
 
@@ -108,6 +130,10 @@ impl Keyboard<USizeableDWORD, InputEvent, LRESULT> for Device {
     fn block_key(&self) -> Result<LRESULT> {
         Ok(BLOCK_KEY)
     }
+
+    fn drives_tick(&self) -> bool {
+        true // from TICK_TIMER
+    }
 }
 
 unsafe impl Send for WindowsKeyMaps {
@@ -128,7 +154,12 @@ static ref KEY_MAPPER: Mutex<WindowsKeyMaps> = {
 
     println!("chosen config file: {}", config.config_file);
 
-    Mutex::new(WindowsKeyMaps::from_cfg(&key_map, &config.config_file))
+    Mutex::new(
+        WindowsKeyMaps::from_cfg(&key_map, &config.config_file).unwrap_or_else(|e| {
+            println!("fatal error: {}", e);
+            exit(1);
+        }),
+    )
 };
 }
 
@@ -150,16 +181,49 @@ unsafe extern "system" fn keybd_proc(code: c_int, w_param: WPARAM, l_param: LPAR
         value: w_param,
         kb_hook_pointer: l_param,
         kb_hook_struct: kb_struct,
+        modified: false,
     };
 
     // .unwrap() is ok because windows impl can actually never can fail
     //DEVICE.send(&mut input_event).unwrap()
     //KEY_MAPPER.send_event(&mut input_event, &DEVICE).unwrap()
-    KEY_MAPPER
-        .lock()
-        .unwrap()
-        .send_event(&mut input_event, &DEVICE)
-        .unwrap()
+    let mut key_maps = KEY_MAPPER.lock().unwrap();
+    let ret = key_maps.send_event(&mut input_event, &DEVICE).unwrap();
+    schedule_tick(&key_maps);
+    ret
+}
+
+unsafe extern "system" fn tick_proc(_hwnd: HWND, _msg: UINT, _id: UINT_PTR, _time: DWORD) {
+    let mut key_maps = KEY_MAPPER.lock().unwrap();
+    // only a template to send keys with, so it is never passed on as-is
+    let mut input_event = InputEvent {
+        code: 0,
+        value: WM_KEYDOWN as WPARAM,
+        kb_hook_pointer: 0,
+        kb_hook_struct: zeroed(),
+        modified: true,
+    };
+    key_maps.tick(&mut input_event, &DEVICE).unwrap();
+    schedule_tick(&key_maps);
+}
+
+// KeyMaps only sees time pass when tick is called, so keep a timer set for when it next needs to be
+fn schedule_tick(key_maps: &WindowsKeyMaps) {
+    let timer = TICK_TIMER.load(Ordering::Relaxed);
+    match key_maps.timeout() {
+        Some(timeout) => {
+            // rounded up so it doesn't fire before the timeout passes
+            let ms = ((timeout.as_micros() + 999) / 1000).min(UINT::MAX as u128) as UINT;
+            // passing the id of the timer already set replaces it instead of adding another
+            let timer = unsafe { SetTimer(null_mut(), timer, ms, Some(tick_proc)) };
+            TICK_TIMER.store(timer, Ordering::Relaxed);
+        }
+        None if timer != 0 => {
+            unsafe { KillTimer(null_mut(), timer) };
+            TICK_TIMER.store(0, Ordering::Relaxed);
+        }
+        None => (),
+    }
 }
 
 fn set_hook(
@@ -207,8 +271,11 @@ pub fn main_res() -> Result<()> {
         ShowWindow(hwnd, SW_HIDE);
     }
 
+    // the hook is called while we wait in here, and the tick timer is called from dispatching its messages
     let mut msg: MSG = unsafe { zeroed() };
-    unsafe { GetMessageW(&mut msg, 0 as HWND, 0, 0) };
+    while unsafe { GetMessageW(&mut msg, 0 as HWND, 0, 0) } > 0 {
+        unsafe { DispatchMessageW(&msg) };
+    }
 
     //std::thread::sleep(std::time::Duration::from_millis(400000));
 